@@ -18,10 +18,37 @@ pub struct Options {
     #[arg()]
     pub elf: Option<PathBuf>,
 
+    /// Render whatever can be recovered from a truncated or corrupted ELF,
+    /// instead of failing on the first inconsistency.
+    #[arg(long, global = true)]
+    pub lenient: bool,
+
+    /// When the ELF has no section header, reconstruct synthetic sections
+    /// from the program headers and the dynamic section.
+    #[arg(long, global = true)]
+    pub recover_sections: bool,
+
+    /// How to demangle symbol names.
+    #[arg(long, global = true, value_enum, default_value_t = DemangleMode::default())]
+    pub demangle: DemangleMode,
+
     #[command(subcommand)]
     pub command: Option<Command>,
 }
 
+#[derive(Clone, Copy, ValueEnum, Debug, Default)]
+pub enum DemangleMode {
+    /// Rust or C++, depending on the mangling scheme
+    #[default]
+    Auto,
+    /// Rust (legacy and v0)
+    Rust,
+    /// C++ (Itanium ABI)
+    Cpp,
+    /// Don't demangle
+    None,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// Give a brief summary of the ELF: file header, program headers, and
@@ -39,6 +66,14 @@ pub enum Command {
     /// List all sections.
     Sections,
 
+    /// Draw the virtual address space: loadable segments, the sections they
+    /// contain, and the section to segment mapping.
+    Map,
+
+    /// Check the structural consistency of the ELF, and report malformed or
+    /// suspicious constructs.
+    Lint,
+
     /// Display detailed information of one specific section, including its
     /// content. The formatting used depends on the type of section.
     #[clap(alias = "sh")]
@@ -51,6 +86,35 @@ pub enum Command {
     /// Disassemble a function.
     Fn(FnArgs),
 
+    /// Disassemble a whole section, or a range of virtual addresses.
+    Disasm(DisasmArgs),
+
+    /// List the instructions that reference a function or a variable.
+    Xrefs(XrefsArgs),
+
+    /// Output the static call graph of the binary.
+    Callgraph(CallgraphArgs),
+
+    /// Search executable sections for a byte pattern or for instructions.
+    Grep(GrepArgs),
+
+    /// Extract printable strings from all sections, or from segments.
+    Strings(StringsArgs),
+
+    /// Hexdump a range of virtual addresses, or a symbol's content.
+    Dump(DumpArgs),
+
+    /// Display the content of a data symbol, decoded with its DWARF type.
+    Obj(ObjArgs),
+
+    /// Write bytes or instructions at an address, a symbol, or in a section,
+    /// into a copy of the ELF.
+    Patch(PatchArgs),
+
+    /// Write the loadable segments to a flat binary image, Intel HEX, or
+    /// Motorola S-record file.
+    Export(ExportArgs),
+
     /// List all relocation entries.
     #[clap(alias = "rel")]
     Relocations,
@@ -100,6 +164,53 @@ pub struct SymbolsArgs {
     /// Only display symbols of a specific type.
     #[arg(long, short = 't')]
     pub r#type: Option<SymbolType>,
+
+    /// Sort the symbols, instead of listing them in the table's order.
+    #[arg(long, value_enum)]
+    pub sort: Option<SymbolSort>,
+
+    /// Reverse the order of the listing.
+    #[arg(long, short = 'r')]
+    pub reverse: bool,
+
+    /// The columns to display, in this order, separated by commas.
+    #[arg(
+        long,
+        short = 'c',
+        value_enum,
+        value_delimiter = ',',
+        default_values_t = [
+            SymbolColumn::Value, SymbolColumn::Type, SymbolColumn::Size, SymbolColumn::Section,
+            SymbolColumn::Name,
+        ],
+    )]
+    pub columns: Vec<SymbolColumn>,
+}
+
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
+pub enum SymbolColumn {
+    /// The symbol's value, usually its address
+    Value,
+    /// Its type, visibility, and binding
+    Type,
+    /// Its size
+    Size,
+    /// The section it belongs to
+    Section,
+    /// Its name
+    Name,
+}
+
+#[derive(Clone, Copy, ValueEnum, Debug)]
+pub enum SymbolSort {
+    /// By value
+    Addr,
+    /// By size
+    Size,
+    /// By name; demangled names are sorted, unless `--no-demangle` is given
+    Name,
+    /// By type, then by value
+    Type,
 }
 
 #[derive(Clone, ValueEnum, Debug)]
@@ -152,6 +263,21 @@ pub struct SectionArgs {
     /// A number of bytes to skip for export or hexdump.
     #[arg(short = 's', long)]
     pub skip: Option<usize>,
+
+    /// Label the hexdump's rows with file offsets instead of virtual
+    /// addresses.
+    #[arg(long)]
+    pub file_offsets: bool,
+
+    /// Group the hexdump's bytes as words of 1, 2, 4, or 8 bytes, in the ELF's
+    /// endianness.
+    #[arg(long, short = 'w', value_parser = parse_word_size, default_value = "1")]
+    pub word: usize,
+
+    /// Look a dynamic symbol up in a `.hash` or `.gnu.hash` section, walking
+    /// the table as the dynamic linker does.
+    #[arg(long, conflicts_with = "hexdump")]
+    pub lookup: Option<String>,
 }
 
 #[derive(Clone, Copy, ValueEnum, Debug, Default)]
@@ -165,9 +291,20 @@ pub enum Syntax {
 
 #[derive(Args, Debug)]
 pub struct FnArgs {
+    /// The symbol's name, raw or demangled. If no symbol has this exact name,
+    /// the symbols whose name contains it are searched for.
     #[arg()]
     pub name: String,
 
+    /// Match the name as a regular expression.
+    #[arg(long, short = 'r', conflicts_with = "address")]
+    pub regex: bool,
+
+    /// Disassemble every matching symbol, instead of listing them when the
+    /// name is ambiguous.
+    #[arg(long, conflicts_with_all = ["address", "cfg"])]
+    pub all: bool,
+
     /// The parameter is a memory address, not a symbol name. The address is
     /// always parsed as hexadecimal, and can start with `0x` or not.
     #[arg(long, short = 'a')]
@@ -177,11 +314,271 @@ pub struct FnArgs {
     #[arg(long)]
     pub cfi: bool,
 
+    /// Draw arrows from jumps to their targets, and separate basic blocks.
+    #[arg(long, short = 'j')]
+    pub jumps: bool,
+
+    /// Output the function's control-flow graph in Graphviz DOT format
+    /// instead of a listing.
+    #[arg(long, conflicts_with_all = ["cfi", "jumps"])]
+    pub cfg: bool,
+
+    /// Syntax to use to format the disassembly.
+    #[arg(long, value_enum, env = "ELF_INFO_ASM_SYNTAX", default_value_t)]
+    pub syntax: Syntax,
+}
+
+#[derive(Args, Debug)]
+pub struct DisasmArgs {
+    /// The section to disassemble, `.text` if neither a section nor an address
+    /// range is specified.
+    #[arg(conflicts_with = "start")]
+    pub section: Option<String>,
+
+    /// The virtual address to start disassembling from, always parsed as
+    /// hexadecimal.
+    #[arg(long, value_parser = parse_hex)]
+    pub start: Option<u64>,
+
+    /// The virtual address to stop disassembling at (excluded), always parsed
+    /// as hexadecimal. Defaults to the end of the section containing `--start`.
+    #[arg(long, value_parser = parse_hex, requires = "start", conflicts_with = "len")]
+    pub end: Option<u64>,
+
+    /// The number of bytes to disassemble from `--start`.
+    #[arg(long, value_parser = parse_int, requires = "start")]
+    pub len: Option<u64>,
+
+    /// Superimpose call-frame information extracted from `.eh_frame`.
+    #[arg(long)]
+    pub cfi: bool,
+
+    /// Draw arrows from jumps to their targets, and separate basic blocks.
+    #[arg(long, short = 'j')]
+    pub jumps: bool,
+
+    /// Syntax to use to format the disassembly.
+    #[arg(long, value_enum, env = "ELF_INFO_ASM_SYNTAX", default_value_t)]
+    pub syntax: Syntax,
+}
+
+#[derive(Args, Debug)]
+pub struct XrefsArgs {
+    /// The symbol's name; imported functions also match their PLT stub and
+    /// GOT slot.
+    #[arg()]
+    pub name: String,
+
+    /// The parameter is a memory address, not a symbol name. The address is
+    /// always parsed as hexadecimal, and can start with `0x` or not.
+    #[arg(long, short = 'a')]
+    pub address: bool,
+
+    /// List what the function references instead: the functions it calls or
+    /// jumps to, and the data it accesses.
+    #[arg(long)]
+    pub from: bool,
+
     /// Syntax to use to format the disassembly.
     #[arg(long, value_enum, env = "ELF_INFO_ASM_SYNTAX", default_value_t)]
     pub syntax: Syntax,
 }
 
+#[derive(Args, Debug)]
+pub struct CallgraphArgs {
+    /// Output format.
+    #[arg(long, short = 'f', value_enum, default_value_t)]
+    pub format: GraphFormat,
+
+    /// Only include the functions reachable from this one.
+    #[arg(long, short = 'r')]
+    pub root: Option<String>,
+
+    /// Maximum number of calls away from the root function.
+    #[arg(long, short = 'd', requires = "root")]
+    pub depth: Option<usize>,
+
+    /// Don't follow the calls made by functions of Rust's standard, core, and
+    /// alloc libraries.
+    #[arg(long)]
+    pub collapse_std: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum, Debug, Default)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    #[default]
+    Dot,
+    /// JSON
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct GrepArgs {
+    /// Hexadecimal bytes to search for, where `??` matches any byte, e.g.
+    /// `"e8 ?? ?? ?? ??"`. With `--insn`, a regex matched against each
+    /// disassembled instruction instead, e.g. `"lock cmpxchg"`.
+    #[arg()]
+    pub pattern: String,
+
+    /// The pattern is a regex matching instructions, not bytes.
+    #[arg(long, short = 'i')]
+    pub insn: bool,
+
+    /// Number of instructions to display before and after each match.
+    #[arg(long, short = 'C', default_value_t = 2)]
+    pub context: usize,
+
+    /// Syntax to use to format the disassembly.
+    #[arg(long, value_enum, env = "ELF_INFO_ASM_SYNTAX", default_value_t)]
+    pub syntax: Syntax,
+}
+
+#[derive(Args, Debug)]
+pub struct StringsArgs {
+    /// Only extract strings from this section.
+    #[arg(long, short = 's', conflicts_with = "segments")]
+    pub section: Option<String>,
+
+    /// Extract strings from `PT_LOAD` segments instead of sections.
+    #[arg(long)]
+    pub segments: bool,
+
+    /// The minimum number of characters of a string.
+    #[arg(long, short = 'n', default_value_t = 4)]
+    pub min_len: usize,
+
+    /// The encodings to look for, separated by commas. UTF-8 includes ASCII.
+    #[arg(long, short = 'e', value_enum, value_delimiter = ',', default_value = "utf8")]
+    pub encoding: Vec<StringEncoding>,
+
+    /// Only display the strings referenced from code, with a RIP-relative or
+    /// absolute memory operand, or an immediate operand.
+    #[arg(long, short = 'r')]
+    pub referenced: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq)]
+pub enum StringEncoding {
+    Ascii,
+    Utf8,
+    Utf16,
+}
+
+#[derive(Args, Debug)]
+pub struct DumpArgs {
+    /// The virtual address to start dumping from, always parsed as
+    /// hexadecimal.
+    #[arg(long, value_parser = parse_hex, required_unless_present = "sym")]
+    pub start: Option<u64>,
+
+    /// The virtual address to stop dumping at (excluded), always parsed as
+    /// hexadecimal.
+    #[arg(long, value_parser = parse_hex, requires = "start", conflicts_with = "len")]
+    pub end: Option<u64>,
+
+    /// The number of bytes to dump; 256 by default, or the symbol's size.
+    #[arg(long, short = 'n', value_parser = parse_int)]
+    pub len: Option<u64>,
+
+    /// Dump the content of this symbol.
+    #[arg(long, conflicts_with = "start")]
+    pub sym: Option<String>,
+
+    /// Label the rows with file offsets instead of virtual addresses.
+    #[arg(long)]
+    pub file_offsets: bool,
+
+    /// Group bytes as words of 1, 2, 4, or 8 bytes, in the ELF's endianness.
+    #[arg(long, short = 'w', value_parser = parse_word_size, default_value = "1")]
+    pub word: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct ObjArgs {
+    /// The symbol's name.
+    #[arg()]
+    pub name: String,
+
+    /// Only hexdump the content, without decoding it from DWARF type
+    /// information.
+    #[arg(long)]
+    pub raw: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct PatchArgs {
+    /// The virtual address to patch, always parsed as hexadecimal.
+    #[arg(
+        long,
+        value_parser = parse_hex,
+        required_unless_present_any = ["sym", "section"],
+        conflicts_with_all = ["sym", "section"],
+    )]
+    pub addr: Option<u64>,
+
+    /// The symbol to patch, optionally followed by an offset, e.g.
+    /// `CONFIG+0x10`.
+    #[arg(long, conflicts_with = "section")]
+    pub sym: Option<String>,
+
+    /// The section to patch, optionally followed by an offset, e.g.
+    /// `.rodata+64`.
+    #[arg(long)]
+    pub section: Option<String>,
+
+    /// Hexadecimal bytes to write, where `??` keeps the original byte, e.g.
+    /// `"90 90 ?? c3"`.
+    #[arg(long, short = 'b', required_unless_present = "asm", conflicts_with = "asm")]
+    pub bytes: Option<String>,
+
+    /// x86 instructions to assemble and write, in Intel syntax and separated
+    /// by `;`, e.g. `"xor eax, eax; ret"`.
+    #[arg(long, short = 'a')]
+    pub asm: Option<String>,
+
+    /// The file to write the patched copy of the ELF to.
+    #[arg(long, short = 'o')]
+    pub output: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// The file to write the image to.
+    #[arg(long, short = 'o')]
+    pub output: PathBuf,
+
+    /// The output format.
+    #[arg(long, short = 'O', value_enum, default_value_t = ExportFormat::default())]
+    pub format: ExportFormat,
+
+    /// Lay segments out at their virtual addresses instead of their physical
+    /// (load) addresses.
+    #[arg(long)]
+    pub r#virtual: bool,
+
+    /// The address of the image's first byte, always parsed as hexadecimal.
+    /// Defaults to the lowest segment address.
+    #[arg(long, value_parser = parse_hex)]
+    pub base: Option<u64>,
+
+    /// The byte to fill gaps between segments with, defaults to 0 for binary
+    /// images. Hex formats leave gaps out unless it is specified.
+    #[arg(long, value_parser = parse_byte)]
+    pub gap_fill: Option<u8>,
+}
+
+#[derive(Clone, Copy, ValueEnum, Debug, Default)]
+pub enum ExportFormat {
+    /// Raw binary
+    #[default]
+    Binary,
+    /// Intel HEX
+    Ihex,
+    /// Motorola S-record
+    Srec,
+}
+
 #[derive(Args, Debug, Clone, Default)]
 pub struct EhArgs {
     /// A specific section name to parse for call frame information entries.
@@ -190,11 +587,47 @@ pub struct EhArgs {
     #[arg(long)]
     pub section: Option<String>,
 
-    /// Only display FDEs that contains the address of this symbol.
+    /// Only display FDEs that contains the address of this symbol. If no
+    /// symbol has this exact name, the symbols whose name contains it are
+    /// searched for.
     #[arg(long, short = 's')]
     pub symbol: Option<String>,
 
+    /// Match the symbol name as a regular expression.
+    #[arg(long, short = 'r', requires = "symbol")]
+    pub regex: bool,
+
     /// Only display FDEs that contains this address.
     #[arg(long)]
     pub address: Option<u64>,
+
+    /// Verify that the `.eh_frame_hdr` lookup table is consistent with the
+    /// FDEs, and report functions that have no FDE.
+    #[arg(long)]
+    pub check: bool,
+}
+
+/// Parse a hexadecimal number, with or without a `0x` prefix.
+fn parse_hex(s: &str) -> Result<u64, String> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16)
+        .map_err(|e| format!("invalid hexadecimal number: {e}"))
+}
+
+/// Parse a decimal number, or hexadecimal if prefixed with `0x`.
+fn parse_int(s: &str) -> Result<u64, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }.map_err(|e| format!("invalid number: {e}"))
+}
+
+fn parse_byte(s: &str) -> Result<u8, String> {
+    u8::try_from(parse_int(s)?).map_err(|_| "the value must fit in a byte".to_string())
+}
+
+fn parse_word_size(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(n @ (1 | 2 | 4 | 8)) => Ok(n),
+        _ => Err("the word size must be 1, 2, 4, or 8".to_string()),
+    }
 }
@@ -0,0 +1,395 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! A minimal assembler for x86 instructions written in Intel syntax: every
+//! encoding of the mnemonic accepting the operands is tried with iced's
+//! encoder, and the shortest one is kept.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::OnceLock;
+use anyhow::{anyhow, bail, Result};
+use iced_x86::{Code, Decoder, DecoderOptions, Encoder, Instruction, MemoryOperand, Mnemonic,
+               OpCodeOperandKind, Register};
+
+enum Operand {
+    Reg(Register),
+    Imm(i64),
+    Mem {
+        mem: MemoryOperand,
+        size: Option<usize>,
+        /// A displacement relative to the next instruction, written
+        /// `[rip + N]`.
+        rip_offset: Option<i64>,
+    },
+}
+
+/// Assemble instructions separated by `;`, the first one being located at
+/// `ip`. `resolve` gives the address of symbols used as operands.
+pub fn assemble(
+    text: &str,
+    bitness: u32,
+    ip: u64,
+    resolve: &dyn Fn(&str) -> Option<u64>,
+) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+
+    for line in text.split([';', '\n']).map(str::trim).filter(|l| !l.is_empty()) {
+        let bytes = assemble_one(line, bitness, ip + out.len() as u64, resolve)
+            .map_err(|e| anyhow!("{line:?}: {e}"))?;
+        out.extend(bytes);
+    }
+    if out.is_empty() {
+        bail!("no instruction to assemble");
+    }
+
+    Ok(out)
+}
+
+fn assemble_one(
+    line: &str,
+    bitness: u32,
+    ip: u64,
+    resolve: &dyn Fn(&str) -> Option<u64>,
+) -> Result<Vec<u8>> {
+    let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let mnemonic = find_mnemonic(mnemonic)?;
+    let operands = split_operands(rest)
+        .into_iter()
+        .map(|op| parse_operand(op, bitness, resolve))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Without a register, the size of a memory operand must be explicit.
+    let unsized_mem = operands.iter().any(|op| matches!(op, Operand::Mem { size: None, .. }))
+        && !operands.iter().any(|op| matches!(op, Operand::Reg(_)));
+
+    let mut best: Option<Vec<u8>> = None;
+    let mut mem_sizes = BTreeSet::new();
+    for code in Code::values() {
+        if code.mnemonic() != mnemonic {
+            continue;
+        }
+        let op_code = code.op_code();
+        let mode = match bitness {
+            64 => op_code.mode64(),
+            32 => op_code.mode32(),
+            _ => op_code.mode16(),
+        };
+        if !op_code.is_instruction() || !mode || op_code.op_count() as usize != operands.len() {
+            continue;
+        }
+        // In 64-bit mode, `mov` with a memory offset needs a 64-bit address or
+        // an `addr32` prefix; assemblers use a SIB byte instead.
+        if bitness == 64 && op_code.op_kinds().contains(&OpCodeOperandKind::mem_offs) {
+            continue;
+        }
+
+        let Some((instr, bytes)) = encode(code, &operands, bitness, ip) else { continue };
+        let mem_size = instr.memory_size().size();
+        let size_matches = operands.iter().all(|op| match op {
+            Operand::Mem { size: Some(size), .. } => *size == mem_size,
+            _ => true,
+        });
+        if !size_matches {
+            continue;
+        }
+
+        mem_sizes.insert(mem_size);
+        if best.as_ref().is_none_or(|best| bytes.len() < best.len()) {
+            best = Some(bytes);
+        }
+    }
+
+    if unsized_mem && mem_sizes.len() > 1 {
+        bail!("ambiguous operand size, specify one with e.g. `dword ptr`");
+    }
+    best.ok_or_else(|| anyhow!("no encoding matches these operands"))
+}
+
+fn encode(code: Code, operands: &[Operand], bitness: u32, ip: u64) -> Option<(Instruction, Vec<u8>)> {
+    let mut instr = build(code, operands)?;
+    let mut bytes = encode_at(&instr, bitness, ip)?;
+
+    // RIP-relative operands are encoded relative to the next instruction,
+    // whose address is only known once encoded.
+    let rip_offset = operands.iter().find_map(|op| match op {
+        Operand::Mem { rip_offset, .. } => *rip_offset,
+        _ => None,
+    });
+    if let Some(offset) = rip_offset {
+        let next_ip = ip + bytes.len() as u64;
+        instr.set_memory_displacement64(next_ip.wrapping_add(offset as u64));
+        bytes = encode_at(&instr, bitness, ip)?;
+    }
+
+    // Guard against encodings that would decode to another instruction.
+    let decoded = Decoder::with_ip(bitness, &bytes, ip, DecoderOptions::NONE).decode();
+    (decoded.code() == code && decoded.len() == bytes.len()).then_some((instr, bytes))
+}
+
+fn encode_at(instr: &Instruction, bitness: u32, ip: u64) -> Option<Vec<u8>> {
+    let mut encoder = Encoder::new(bitness);
+    encoder.encode(instr, ip).ok()?;
+    Some(encoder.take_buffer())
+}
+
+fn build(code: Code, operands: &[Operand]) -> Option<Instruction> {
+    use Operand::*;
+
+    let is_branch = code.op_code().op_kinds().first().is_some_and(|kind| matches!(
+        kind,
+        OpCodeOperandKind::br16_1 | OpCodeOperandKind::br32_1 | OpCodeOperandKind::br64_1
+            | OpCodeOperandKind::br16_2 | OpCodeOperandKind::br32_4
+            | OpCodeOperandKind::br64_4,
+    ));
+    if is_branch {
+        return match operands {
+            [Imm(target)] => Instruction::with_branch(code, *target as u64).ok(),
+            _ => None,
+        };
+    }
+
+    let imm32 = |i: &i64| i32::try_from(*i).ok();
+    let mem = |op: &Operand| match op {
+        Mem { mem, .. } => Some(*mem),
+        _ => None,
+    };
+
+    let instr = match operands {
+        [] => Ok(Instruction::with(code)),
+        [Reg(r)] => Instruction::with1(code, *r),
+        [Imm(i)] => Instruction::with1(code, imm32(i)?),
+        [m @ Mem { .. }] => Instruction::with1(code, mem(m)?),
+        [Reg(a), Reg(b)] => Instruction::with2(code, *a, *b),
+        [Reg(r), Imm(i)] => Instruction::with2(code, *r, *i),
+        [Reg(r), m @ Mem { .. }] => Instruction::with2(code, *r, mem(m)?),
+        [m @ Mem { .. }, Reg(r)] => Instruction::with2(code, mem(m)?, *r),
+        [m @ Mem { .. }, Imm(i)] => Instruction::with2(code, mem(m)?, imm32(i)?),
+        [Imm(i), Reg(r)] => Instruction::with2(code, imm32(i)?, *r),
+        [Imm(a), Imm(b)] => Instruction::with2(code, imm32(a)?, imm32(b)?),
+        [Reg(a), Reg(b), Reg(c)] => Instruction::with3(code, *a, *b, *c),
+        [Reg(a), Reg(b), Imm(i)] => Instruction::with3(code, *a, *b, imm32(i)?),
+        [Reg(a), Reg(b), m @ Mem { .. }] => Instruction::with3(code, *a, *b, mem(m)?),
+        [Reg(a), m @ Mem { .. }, Reg(b)] => Instruction::with3(code, *a, mem(m)?, *b),
+        [Reg(r), m @ Mem { .. }, Imm(i)] => Instruction::with3(code, *r, mem(m)?, imm32(i)?),
+        [m @ Mem { .. }, Reg(a), Reg(b)] => Instruction::with3(code, mem(m)?, *a, *b),
+        [m @ Mem { .. }, Reg(r), Imm(i)] => Instruction::with3(code, mem(m)?, *r, imm32(i)?),
+        _ => return None,
+    };
+
+    instr.ok()
+}
+
+fn find_mnemonic(s: &str) -> Result<Mnemonic> {
+    static MNEMONICS: OnceLock<HashMap<String, Mnemonic>> = OnceLock::new();
+    let mnemonics = MNEMONICS.get_or_init(|| {
+        Mnemonic::values()
+            .map(|m| (format!("{m:?}").to_lowercase(), m))
+            .collect()
+    });
+
+    let s = s.to_lowercase();
+    let s = match s.as_str() {
+        "jz" => "je",
+        "jnz" => "jne",
+        "jc" | "jnae" => "jb",
+        "jnc" | "jnb" => "jae",
+        "jna" => "jbe",
+        "jnbe" => "ja",
+        "jnge" => "jl",
+        "jnl" => "jge",
+        "jng" => "jle",
+        "jnle" => "jg",
+        "jpe" => "jp",
+        "jpo" => "jnp",
+        "setz" => "sete",
+        "setnz" => "setne",
+        "cmovz" => "cmove",
+        "cmovnz" => "cmovne",
+        s => s,
+    };
+
+    mnemonics.get(s)
+        .copied()
+        .ok_or_else(|| anyhow!("unknown mnemonic {s:?}"))
+}
+
+fn find_register(s: &str) -> Option<Register> {
+    static REGISTERS: OnceLock<HashMap<String, Register>> = OnceLock::new();
+    let registers = REGISTERS.get_or_init(|| {
+        Register::values()
+            .filter(|&r| r != Register::None)
+            .map(|r| (format!("{r:?}").to_lowercase(), r))
+            .collect()
+    });
+
+    registers.get(&s.to_lowercase()).copied()
+}
+
+/// Split operands on commas, except within brackets.
+fn split_operands(s: &str) -> Vec<&str> {
+    let mut operands = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                operands.push(s[start..i].trim());
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    if !s[start..].trim().is_empty() {
+        operands.push(s[start..].trim());
+    }
+
+    operands
+}
+
+fn parse_number(s: &str) -> Option<i64> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s.trim()),
+        None => (false, s),
+    };
+    let n = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()? as i64,
+        None => s.parse().ok()?,
+    };
+    Some(if neg { n.wrapping_neg() } else { n })
+}
+
+fn parse_operand(
+    s: &str,
+    bitness: u32,
+    resolve: &dyn Fn(&str) -> Option<u64>,
+) -> Result<Operand> {
+    let s = s.trim();
+    let lower = s.to_ascii_lowercase();
+    let sizes = [
+        ("byte", 1), ("word", 2), ("dword", 4), ("qword", 8), ("tbyte", 10),
+        ("xmmword", 16), ("ymmword", 32), ("zmmword", 64),
+    ];
+    let (size, rest) = sizes.iter()
+        .find_map(|&(name, size)| {
+            let rest = lower.strip_prefix(name)?.trim_start().strip_prefix("ptr")?;
+            Some((Some(size), s[(s.len() - rest.len())..].trim()))
+        })
+        .unwrap_or((None, s));
+
+    let (segment, rest) = match rest.split_once(':') {
+        Some((seg, rest)) if rest.trim_start().starts_with('[') => {
+            let seg = find_register(seg.trim())
+                .ok_or_else(|| anyhow!("invalid segment {seg:?}"))?;
+            (seg, rest.trim())
+        },
+        _ => (Register::None, rest),
+    };
+
+    if let Some(inner) = rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+        return parse_memory(inner, size, segment, bitness, resolve);
+    }
+    if size.is_some() {
+        bail!("expected a memory operand after `ptr`: {s:?}");
+    }
+
+    if let Some(reg) = find_register(rest) {
+        return Ok(Operand::Reg(reg));
+    }
+    if let Some(n) = parse_number(rest) {
+        return Ok(Operand::Imm(n));
+    }
+    resolve(rest)
+        .map(|addr| Operand::Imm(addr as i64))
+        .ok_or_else(|| anyhow!("invalid operand {s:?}"))
+}
+
+fn parse_memory(
+    inner: &str,
+    size: Option<usize>,
+    segment: Register,
+    bitness: u32,
+    resolve: &dyn Fn(&str) -> Option<u64>,
+) -> Result<Operand> {
+    let mut base = Register::None;
+    let mut index = Register::None;
+    let mut scale = 1;
+    let mut displ: i64 = 0;
+    let mut has_displ = false;
+
+    // Split into signed terms.
+    let mut terms = Vec::new();
+    let mut sign = 1;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        if c == '+' || c == '-' {
+            terms.push((sign, inner[start..i].trim()));
+            sign = if c == '-' { -1 } else { 1 };
+            start = i + 1;
+        }
+    }
+    terms.push((sign, inner[start..].trim()));
+
+    for (sign, term) in terms.into_iter().filter(|(_, t)| !t.is_empty()) {
+        if let Some((a, b)) = term.split_once('*') {
+            let (reg, n) = match (find_register(a.trim()), find_register(b.trim())) {
+                (Some(reg), None) => (reg, b.trim()),
+                (None, Some(reg)) => (reg, a.trim()),
+                _ => bail!("invalid index {term:?}"),
+            };
+            index = reg;
+            scale = parse_number(n)
+                .filter(|n| matches!(n, 1 | 2 | 4 | 8))
+                .ok_or_else(|| anyhow!("invalid scale {n:?}"))? as u32;
+        } else if let Some(reg) = find_register(term) {
+            if sign < 0 {
+                bail!("registers can't be subtracted");
+            }
+            if base == Register::None {
+                base = reg;
+            } else if index == Register::None {
+                index = reg;
+            } else {
+                bail!("too many registers in memory operand");
+            }
+        } else {
+            let n = parse_number(term)
+                .or_else(|| resolve(term).map(|a| a as i64))
+                .ok_or_else(|| anyhow!("invalid displacement {term:?}"))?;
+            displ = displ.wrapping_add(n.wrapping_mul(sign));
+            has_displ = true;
+        }
+    }
+
+    let mut rip_offset = None;
+    if base == Register::RIP || base == Register::EIP {
+        rip_offset = Some(displ);
+    } else if base == Register::None && index == Register::None && bitness == 64
+        && segment == Register::None {
+        // Absolute addresses are reached relatively to RIP, like `[rel X]`;
+        // those relative to a segment, e.g. TLS slots in `fs:`, stay absolute.
+        base = Register::RIP;
+    }
+
+    let displ_size = match base {
+        Register::None if bitness == 64 => 8,
+        Register::None => 4,
+        _ if has_displ || rip_offset.is_some() => 1,
+        _ => 0,
+    };
+
+    Ok(Operand::Mem {
+        mem: MemoryOperand::new(base, index, scale, displ, displ_size, false, segment),
+        size,
+        rip_offset,
+    })
+}
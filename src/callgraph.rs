@@ -0,0 +1,334 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Static call graph of a whole binary, built from the direct calls and tail
+//! calls found in executable sections.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use anyhow::{anyhow, Result};
+use goblin::elf::Elf;
+use iced_x86::{FlowControl, OpKind};
+
+use crate::args::{CallgraphArgs, GraphFormat};
+use crate::eh::fde_ranges;
+use crate::elf::{content_at_vaddr, find_symbol};
+use crate::flow::dot_escape;
+use crate::func::got_symbols;
+use crate::sym::is_std_sym;
+use crate::xrefs::{decode_code, exec_labels, instr_xref, XrefKind};
+
+struct Node {
+    name: String,
+    addr: Option<u64>,
+    external: bool,
+    indirect_calls: Vec<u64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum EdgeKind {
+    Call,
+    TailCall,
+}
+
+struct CallGraph {
+    nodes: BTreeMap<String, Node>,
+    /// Sites of each `(caller, callee, kind)` edge.
+    edges: BTreeMap<(String, String, EdgeKind), Vec<u64>>,
+}
+
+pub fn callgraph(elf: &Elf, bytes: &[u8], args: &CallgraphArgs) -> Result<()> {
+    let fdes = fde_ranges(elf, bytes);
+    let labels = exec_labels(elf, bytes, &fdes);
+    let mut graph = build_graph(elf, bytes, &labels)?;
+
+    let roots: Vec<String> = match args.root {
+        Some(ref root) => vec![root_id(elf, &labels, root)?],
+        None => graph.nodes.iter()
+            .filter(|(_, n)| !(n.external || args.collapse_std && is_std_sym(&n.name)))
+            .map(|(id, _)| id.clone())
+            .collect(),
+    };
+    let keep = reachable(&graph, &roots, args.depth, args.collapse_std);
+    graph.nodes.retain(|id, _| keep.contains_key(id));
+    graph.edges.retain(|(from, to, _), _| {
+        let expanded = keep.get(from).is_some_and(|&expanded| expanded);
+        expanded && keep.contains_key(to)
+    });
+
+    match args.format {
+        GraphFormat::Dot => print_dot(&graph),
+        GraphFormat::Json => print_json(&graph),
+    }
+
+    Ok(())
+}
+
+fn build_graph(
+    elf: &Elf,
+    bytes: &[u8],
+    labels: &BTreeMap<u64, String>,
+) -> Result<CallGraph> {
+    let got = got_symbols(elf);
+    let relative: HashMap<u64, u64> = elf.dynrelas.iter()
+        .filter(|rel| rel.r_sym == 0)
+        .filter_map(|rel| Some((rel.r_offset, rel.r_addend? as u64)))
+        .collect();
+    let mut graph = CallGraph {
+        nodes: BTreeMap::new(),
+        edges: BTreeMap::new(),
+    };
+
+    for (&addr, name) in labels {
+        let (id, node) = label_node(addr, name);
+        graph.nodes.insert(id, node);
+    }
+
+    decode_code(elf, bytes, |instr| {
+        let site = instr.ip();
+        let Some((&fn_start, fn_name)) = labels.range(..=site).next_back() else {
+            return
+        };
+        if fn_name.ends_with("@plt") {
+            return;
+        }
+        let caller = label_node(fn_start, fn_name).0;
+
+        if instr.flow_control() == FlowControl::IndirectCall && instr_xref(&instr).is_none() {
+            if let Some(node) = graph.nodes.get_mut(&caller) {
+                node.indirect_calls.push(site);
+            }
+            return;
+        }
+
+        let Some((kind, mut target)) = instr_xref(&instr) else { return };
+        if instr.op0_kind() == OpKind::Memory && !got.contains_key(&target) {
+            // A slot holding the address of a local function.
+            match slot_target(elf, bytes, &relative, target) {
+                Some(addr) if labels.contains_key(&addr) => target = addr,
+                _ => (),
+            }
+        }
+        let (callee, kind) = match kind {
+            XrefKind::Call => match (labels.get(&target), got.get(&target)) {
+                (Some(name), _) => (label_node(target, name), EdgeKind::Call),
+                (None, Some(name)) => (external_node(name), EdgeKind::Call),
+                (None, None) if instr.is_call_near() => {
+                    let node = Node {
+                        name: format!("{target:#x}"),
+                        addr: Some(target),
+                        external: false,
+                        indirect_calls: Vec::new(),
+                    };
+                    ((format!("f_{target:x}"), node), EdgeKind::Call)
+                },
+                (None, None) => {
+                    if let Some(node) = graph.nodes.get_mut(&caller) {
+                        node.indirect_calls.push(site);
+                    }
+                    return;
+                },
+            },
+            XrefKind::Jump | XrefKind::CondJump if target != fn_start => {
+                match (labels.get(&target), got.get(&target)) {
+                    (Some(name), _) => (label_node(target, name), EdgeKind::TailCall),
+                    (None, Some(name)) => (external_node(name), EdgeKind::TailCall),
+                    (None, None) => return,
+                }
+            },
+            _ => return,
+        };
+
+        let (callee_id, callee) = callee;
+        graph.nodes.entry(callee_id.clone()).or_insert(callee);
+        graph.edges.entry((caller, callee_id, kind)).or_default().push(site);
+    })?;
+
+    Ok(graph)
+}
+
+/// The address stored in a pointer slot, either by a relative relocation or
+/// in the file's content.
+fn slot_target(
+    elf: &Elf,
+    bytes: &[u8],
+    relative: &HashMap<u64, u64>,
+    slot: u64,
+) -> Option<u64> {
+    if let Some(&addr) = relative.get(&slot) {
+        return Some(addr);
+    }
+
+    let content = content_at_vaddr(elf, bytes, slot)?;
+    let addr = if elf.is_64 {
+        u64::from_le_bytes(content.get(..8)?.try_into().ok()?)
+    } else {
+        u32::from_le_bytes(content.get(..4)?.try_into().ok()?) as u64
+    };
+
+    (addr != 0).then_some(addr)
+}
+
+/// The node of a code label; PLT stubs stand for the imported function.
+fn label_node(addr: u64, name: &str) -> (String, Node) {
+    match name.strip_suffix("@plt") {
+        Some(imported) => external_node(imported),
+        None => (format!("f_{addr:x}"), Node {
+            name: name.to_owned(),
+            addr: Some(addr),
+            external: false,
+            indirect_calls: Vec::new(),
+        }),
+    }
+}
+
+fn external_node(name: &str) -> (String, Node) {
+    (format!("ext_{name}"), Node {
+        name: crate::demangle::demangle(name).to_string(),
+        addr: None,
+        external: true,
+        indirect_calls: Vec::new(),
+    })
+}
+
+fn root_id(elf: &Elf, labels: &BTreeMap<u64, String>, root: &str) -> Result<String> {
+    let addr = find_symbol(&elf.syms, &elf.strtab, root)
+        .filter(|sym| sym.st_shndx != 0)
+        .map(|sym| sym.st_value)
+        .or_else(|| labels.iter().find(|(_, name)| *name == root).map(|(&a, _)| a))
+        .or_else(|| {
+            // Rust symbols without their hash.
+            labels.iter()
+                .find(|(_, name)| name.rsplit_once("::h").is_some_and(|(n, _)| n == root))
+                .map(|(&a, _)| a)
+        })
+        .ok_or_else(|| anyhow!("couldn't find any function matching {root:?}"))?;
+    let name = labels.get(&addr).map(String::as_str).unwrap_or(root);
+
+    Ok(label_node(addr, name).0)
+}
+
+/// Walk the graph from `roots`, up to `max_depth` calls away. Returns the
+/// visited nodes, and whether their callees were followed.
+fn reachable(
+    graph: &CallGraph,
+    roots: &[String],
+    max_depth: Option<usize>,
+    collapse_std: bool,
+) -> HashMap<String, bool> {
+    let mut callees: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for (from, to, _) in graph.edges.keys() {
+        callees.entry(from).or_default().insert(to);
+    }
+
+    let mut visited = HashMap::new();
+    let mut queue: VecDeque<(&str, usize)> = roots.iter()
+        .map(|r| (r.as_str(), 0))
+        .collect();
+
+    while let Some((id, depth)) = queue.pop_front() {
+        if visited.contains_key(id) {
+            continue;
+        }
+        let is_std = graph.nodes.get(id).is_some_and(|n| is_std_sym(&n.name));
+        let expand = max_depth.is_none_or(|max| depth < max)
+            && !(collapse_std && is_std);
+        visited.insert(id.to_owned(), expand);
+
+        if expand {
+            for &callee in callees.get(id).into_iter().flatten() {
+                queue.push_back((callee, depth + 1));
+            }
+        }
+    }
+
+    visited
+}
+
+fn print_dot(graph: &CallGraph) {
+    println!("digraph callgraph {{");
+    println!("    node [shape=box, fontname=\"monospace\"];");
+
+    for (id, node) in &graph.nodes {
+        let mut attrs = format!("label=\"{}\"", dot_escape(&node.name));
+        if node.external {
+            attrs.push_str(", style=dashed");
+        }
+        if !node.indirect_calls.is_empty() {
+            attrs.push_str(&format!(
+                ", xlabel=\"{} indirect\", color=orange",
+                node.indirect_calls.len(),
+            ));
+        }
+        println!("    \"{}\" [{attrs}];", dot_escape(id));
+    }
+
+    for (from, to, kind) in graph.edges.keys() {
+        let attrs = match kind {
+            EdgeKind::Call => "",
+            EdgeKind::TailCall => " [style=dashed, label=\"tail\"]",
+        };
+        println!("    \"{}\" -> \"{}\"{attrs};", dot_escape(from), dot_escape(to));
+    }
+
+    println!("}}");
+}
+
+fn print_json(graph: &CallGraph) {
+    println!("{{");
+    println!("  \"nodes\": [");
+    for (i, (id, node)) in graph.nodes.iter().enumerate() {
+        let sep = if i + 1 < graph.nodes.len() { "," } else { "" };
+        let addr = node.addr.map_or("null".to_owned(), |a| a.to_string());
+        let indirect = node.indirect_calls.iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "    {{\"id\": {}, \"name\": {}, \"address\": {addr}, \
+             \"external\": {}, \"indirect_calls\": [{indirect}]}}{sep}",
+            json_str(id), json_str(&node.name), node.external,
+        );
+    }
+    println!("  ],");
+
+    println!("  \"edges\": [");
+    for (i, ((from, to, kind), sites)) in graph.edges.iter().enumerate() {
+        let sep = if i + 1 < graph.edges.len() { "," } else { "" };
+        let kind = match kind {
+            EdgeKind::Call => "call",
+            EdgeKind::TailCall => "tail",
+        };
+        let sites = sites.iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "    {{\"from\": {}, \"to\": {}, \"kind\": \"{kind}\", \
+             \"sites\": [{sites}]}}{sep}",
+            json_str(from), json_str(to),
+        );
+    }
+    println!("  ]");
+    println!("}}");
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
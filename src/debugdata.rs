@@ -0,0 +1,86 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! MiniDebugInfo: stripped binaries may embed, in `.gnu_debugdata`, an
+//! xz-compressed ELF whose `.symtab` holds the symbols missing from
+//! `.dynsym`.
+
+use std::io;
+use anyhow::{anyhow, bail, Context, Result};
+use goblin::elf::Elf;
+
+use crate::lenient::parse_lenient;
+use crate::sections::find_section;
+
+/// Embedded ELFs decompressing to more than this are refused, as
+/// `.gnu_debugdata` comes from an untrusted file.
+const MAX_EMBEDDED_SIZE: usize = 64 << 20;
+
+/// A buffer refusing to grow past `MAX_EMBEDDED_SIZE`.
+#[derive(Default)]
+struct Capped {
+    data: Vec<u8>,
+    overflowed: bool,
+}
+
+impl io::Write for Capped {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.data.len() + buf.len() > MAX_EMBEDDED_SIZE {
+            self.overflowed = true;
+            return Err(io::Error::other("output too large"));
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decompress the ELF embedded in `.gnu_debugdata`, when the ELF has no
+/// `.symtab` of its own.
+pub fn mini_debug_info(elf: &Elf, bytes: &[u8]) -> Result<Option<Vec<u8>>> {
+    if !elf.syms.is_empty() {
+        return Ok(None);
+    }
+    let Some(sh) = find_section(elf, ".gnu_debugdata") else {
+        return Ok(None);
+    };
+
+    let mut compressed = sh.file_range()
+        .and_then(|r| bytes.get(r))
+        .ok_or_else(|| anyhow!("`.gnu_debugdata` has no content"))?;
+    let mut embedded = Capped::default();
+    let res = lzma_rs::xz_decompress(&mut compressed, &mut embedded);
+    if embedded.overflowed {
+        bail!(
+            "`.gnu_debugdata` decompresses to more than {} MiB, it was ignored",
+            MAX_EMBEDDED_SIZE >> 20,
+        );
+    }
+    res.map_err(|e| anyhow!("{e:?}"))
+        .context("couldn't decompress `.gnu_debugdata`")?;
+
+    Ok(Some(embedded.data))
+}
+
+/// Use the symbol table of the embedded ELF as the ELF's own, and return the
+/// number of symbols.
+pub fn merge_symbols<'a>(elf: &mut Elf<'a>, embedded: &'a [u8]) -> Result<usize> {
+    // Most of the embedded ELF's sections are NOBITS: only read its symbols.
+    let (mini, _) = parse_lenient(embedded)
+        .context("couldn't parse the ELF embedded in `.gnu_debugdata`")?;
+
+    elf.syms = mini.syms;
+    elf.strtab = mini.strtab;
+
+    Ok(elf.syms.len())
+}
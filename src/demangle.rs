@@ -0,0 +1,73 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Demangling of Rust and C++ (Itanium ABI) symbol names, with the scheme
+//! chosen once for the whole program by `--demangle`.
+
+use std::fmt::{self, Display, Formatter};
+use std::sync::OnceLock;
+use cpp_demangle::{DemangleOptions, Symbol};
+
+use crate::args::DemangleMode;
+
+static MODE: OnceLock<DemangleMode> = OnceLock::new();
+
+pub fn set_demangle_mode(mode: DemangleMode) {
+    let _ = MODE.set(mode);
+}
+
+/// A symbol name, demangled when displayed. The alternate format (`{:#}`)
+/// omits Rust's hashes.
+pub enum Demangled<'a> {
+    Rust(rustc_demangle::Demangle<'a>),
+    Cpp(String),
+    Raw(&'a str),
+}
+
+impl Display for Demangled<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Demangled::Rust(d) if f.alternate() => write!(f, "{d:#}"),
+            Demangled::Rust(d) => write!(f, "{d}"),
+            Demangled::Cpp(s) => f.write_str(s),
+            Demangled::Raw(s) => f.write_str(s),
+        }
+    }
+}
+
+pub fn demangle(name: &str) -> Demangled<'_> {
+    let rust = || rustc_demangle::try_demangle(name).ok().map(Demangled::Rust);
+    let cpp = || Symbol::new(name).ok()
+        .and_then(|sym| sym.demangle(&DemangleOptions::default()).ok())
+        .map(Demangled::Cpp);
+
+    let demangled = match MODE.get().copied().unwrap_or_default() {
+        DemangleMode::None => None,
+        DemangleMode::Rust => rust(),
+        DemangleMode::Cpp => cpp(),
+        // Rust's legacy mangling is Itanium's, with a hash as last element.
+        DemangleMode::Auto if name.starts_with("_R") || has_rust_hash(name) => {
+            rust().or_else(cpp)
+        },
+        DemangleMode::Auto => cpp().or_else(rust),
+    };
+
+    demangled.unwrap_or(Demangled::Raw(name))
+}
+
+/// Whether a name ends with a Rust legacy hash path element, `17h` followed
+/// by 16 hexadecimal digits and `E`, ignoring `.llvm.*`-like suffixes.
+fn has_rust_hash(name: &str) -> bool {
+    let name = name.split('.').next().unwrap_or(name).as_bytes();
+    name.len() >= 20 && name.ends_with(b"E") && {
+        let hash = &name[(name.len() - 20)..(name.len() - 1)];
+        hash.starts_with(b"17h") && hash[3..].iter().all(u8::is_ascii_hexdigit)
+    }
+}
@@ -0,0 +1,164 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Hexdumps of virtual address ranges, annotated with symbols and
+//! relocations.
+
+use std::collections::HashSet;
+use std::ops::Range;
+use anyhow::{anyhow, bail, Result};
+use goblin::container::Container;
+use goblin::elf::Elf;
+use goblin::elf::reloc::r_to_str;
+use goblin::elf::sym::{STT_FILE, STT_SECTION};
+
+use crate::args::DumpArgs;
+use crate::demangle::demangle;
+use crate::elf::{content_at_vaddr, find_symbol, vaddr_file_offset};
+use crate::print::{HexdumpOptions, HexMark, hexdump_with, SizePrint};
+
+/// Colors alternating between consecutive symbols.
+const SYM_COLORS: [&str; 2] = ["36", "34"];
+const RELOC_COLOR: &str = "4;33";
+
+pub fn dump(elf: &Elf, bytes: &[u8], args: &DumpArgs) -> Result<()> {
+    let (start, len) = if let Some(ref name) = args.sym {
+        let sym = find_symbol(&elf.syms, &elf.strtab, name)
+            .or_else(|| find_symbol(&elf.dynsyms, &elf.dynstrtab, name))
+            .filter(|sym| sym.st_shndx != 0)
+            .ok_or_else(|| anyhow!("couldn't find any symbol matching {name:?}"))?;
+        let len = args.len.unwrap_or(sym.st_size);
+        if len == 0 {
+            bail!("symbol {name:?} has no size, specify `--len`");
+        }
+        (sym.st_value, len)
+    } else {
+        let start = args.start.ok_or_else(|| anyhow!("specify `--start` or `--sym`"))?;
+        let len = match (args.end, args.len) {
+            (Some(end), _) if end <= start => {
+                bail!("the end address {end:#x} is before the start address {start:#x}");
+            },
+            (Some(end), _) => end - start,
+            (None, Some(len)) => len,
+            (None, None) => 256,
+        };
+        (start, len)
+    };
+
+    let data = content_at_vaddr(elf, bytes, start)
+        .and_then(|c| c.get(..(len as usize)))
+        .ok_or_else(|| anyhow!(
+            "range {start:#x}..{:#x} is not entirely backed by the file content",
+            start + len,
+        ))?;
+    let file_off = vaddr_file_offset(elf, start)
+        .ok_or_else(|| anyhow!("couldn't find the file offset of {start:#x}"))?;
+
+    hexdump_region(elf, Some(start), file_off, data, args.file_offsets, args.word);
+
+    Ok(())
+}
+
+/// Hexdump `data`, located at `file_off` in the file, and mapped at `vaddr`
+/// if any. Rows are labelled by virtual address unless `file_offsets` is set
+/// or the data isn't mapped in memory.
+pub fn hexdump_region(
+    elf: &Elf,
+    vaddr: Option<u64>,
+    file_off: u64,
+    data: &[u8],
+    file_offsets: bool,
+    word: usize,
+) {
+    let sp = SizePrint::new(elf.header.container().unwrap_or(Container::Big));
+    let mut marks = vaddr
+        .map(|start| hex_marks(elf, start..(start + data.len() as u64)))
+        .unwrap_or_default();
+
+    let base = match vaddr {
+        Some(vaddr) if file_offsets => {
+            // Marks may start before or end after the dumped region.
+            let end = vaddr + data.len() as u64;
+            for mark in &mut marks {
+                let start = mark.range.start.clamp(vaddr, end);
+                let stop = mark.range.end.clamp(vaddr, end);
+                mark.range = (start - vaddr + file_off)..(stop - vaddr + file_off);
+            }
+            file_off
+        },
+        Some(vaddr) => vaddr,
+        None => file_off,
+    };
+
+    hexdump_with(data, &HexdumpOptions {
+        base,
+        sp: Some(sp),
+        word,
+        little_endian: elf.little_endian,
+        marks,
+    });
+}
+
+/// Marks for the symbols and the relocated slots within `range`.
+fn hex_marks(elf: &Elf, range: Range<u64>) -> Vec<HexMark> {
+    let mut syms = Vec::new();
+    let mut seen = HashSet::new();
+
+    let tabs = [(&elf.syms, &elf.strtab), (&elf.dynsyms, &elf.dynstrtab)];
+    for (tab, strtab) in tabs {
+        for sym in tab.iter() {
+            if sym.st_shndx == 0 || matches!(sym.st_type(), STT_FILE | STT_SECTION) {
+                continue;
+            }
+            let Some(name) = strtab.get_at(sym.st_name).filter(|n| !n.is_empty()) else {
+                continue
+            };
+            let sym_range = sym.st_value..sym.st_value.saturating_add(sym.st_size.max(1));
+            if sym_range.start < range.end && sym_range.end > range.start
+                && seen.insert((sym.st_value, name)) {
+                syms.push((sym_range, demangle(name).to_string()));
+            }
+        }
+    }
+    syms.sort_by_key(|(r, _)| r.start);
+
+    let mut marks: Vec<HexMark> = syms.into_iter()
+        .enumerate()
+        .map(|(i, (range, name))| HexMark {
+            range,
+            color: SYM_COLORS[i % SYM_COLORS.len()],
+            note: name,
+        })
+        .collect();
+
+    let ptr_size = if elf.is_64 { 8 } else { 4 };
+    let relocs = elf.dynrelas.iter()
+        .chain(elf.dynrels.iter())
+        .chain(elf.pltrelocs.iter())
+        .filter(|rel| range.contains(&rel.r_offset));
+    for rel in relocs {
+        let target = elf.dynsyms.get(rel.r_sym)
+            .filter(|_| rel.r_sym != 0)
+            .and_then(|sym| elf.dynstrtab.get_at(sym.st_name))
+            .map(|name| demangle(name).to_string())
+            .or_else(|| rel.r_addend.map(|a| format!("{a:#x}")))
+            .unwrap_or_default();
+        marks.push(HexMark {
+            range: rel.r_offset..(rel.r_offset + ptr_size),
+            color: RELOC_COLOR,
+            note: format!(
+                "{} {target}",
+                r_to_str(rel.r_type, elf.header.e_machine).trim_start_matches("R_"),
+            ),
+        });
+    }
+
+    marks
+}
@@ -8,66 +8,111 @@
  * any later version. See LICENSE file for more information.                  *
  ******************************************************************************/
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
 use byteorder::ReadBytesExt;
-use gimli::{BaseAddresses, CallFrameInstruction, CieOrFde,
-            CommonInformationEntry, EhFrame, FrameDescriptionEntry,
-            LittleEndian, Reader, Register, SectionBaseAddresses, UnwindSection,
-            X86_64};
+use gimli::{AArch64, Arm, BaseAddresses, CallFrameInstruction, CieOrFde,
+            CommonInformationEntry, DebugFrame, EhFrame, EndianSlice, FrameDescriptionEntry,
+            LittleEndian, Pointer, Reader, Register, RiscV,
+            SectionBaseAddresses, UnwindSection, X86, X86_64};
 use goblin::container::Container;
-use goblin::elf::Elf;
-use rustc_demangle::demangle;
-use anyhow::{anyhow, Context, Result};
+use goblin::elf::{Elf, SectionHeader};
+use goblin::elf::sym::STT_FUNC;
+use anyhow::{anyhow, bail, Context, Result};
 
 use crate::args::EhArgs;
-use crate::elf::find_symbol;
-use crate::print::{PairTable, SizePrint};
+use crate::demangle::demangle;
+use crate::elf::{content_at_vaddr, match_symbols};
+use crate::lsda::{parse_lsda, print_lsda, read_ptr};
+use crate::print::{PairTable, print_header, SizePrint};
 use crate::sections::find_section;
-use crate::sym::addr_to_sym;
+use crate::sym::{addr_to_sym, print_candidates, sym_name_at};
 
 pub fn eh(elf: &Elf, bytes: &[u8], mut opts: EhArgs) -> Result<()> {
-    let sh = if let Some(ref name) = opts.section {
-        find_section(elf, name)
-            .ok_or_else(|| anyhow!("couldn't find section {name:?}"))?
+    let (name, sh) = if let Some(ref name) = opts.section {
+        (name.as_str(), find_section(elf, name)
+            .ok_or_else(|| anyhow!("couldn't find section {name:?}"))?)
     } else {
-        find_section(elf, ".eh_frame")
-            .or_else(|| find_section(elf, ".debug_frame"))
+        find_section(elf, ".eh_frame").map(|sh| (".eh_frame", sh))
+            .or_else(|| find_section(elf, ".debug_frame").map(|sh| (".debug_frame", sh)))
             .ok_or_else(|| anyhow!("couldn't find section `.eh_frame` or `.debug_frame`"))?
     };
+    let is_debug_frame = name == ".debug_frame";
 
-    let range = sh.file_range()
+    let content = sh.file_range()
         .ok_or_else(|| anyhow!("section has no content"))?;
+    let content = bytes.get(content)
+        .ok_or_else(|| anyhow!("section {name:?} goes past the end of the file"))?;
 
     if let Some(ref sym) = opts.symbol {
-        let sym = find_symbol(&elf.syms, &elf.strtab, sym)
-            .or_else(|| find_symbol(&elf.dynsyms, &elf.dynstrtab, sym))
-            .ok_or_else(||
-                anyhow!("couldn't find any symbol matching {:?}", sym)
-            )?;
-        opts.address = Some(sym.st_value);
+        let matches = match_symbols(elf, sym, opts.regex, false)?;
+        match matches[..] {
+            [] => bail!("couldn't find any symbol matching {sym:?}"),
+            [(sym, _)] => opts.address = Some(sym.st_value),
+            _ => {
+                print_candidates(elf, sym, &matches);
+                bail!("{} symbols match {sym:?}", matches.len());
+            },
+        }
     }
 
-    eh_frame(elf, sh.sh_addr, &bytes[range], &opts)?;
+    if opts.check {
+        if is_debug_frame {
+            bail!("`--check` only applies to `.eh_frame`");
+        }
+        return check_eh_frame_hdr(elf, bytes, sh.sh_addr, content);
+    }
+
+    if is_debug_frame {
+        debug_frame(elf, bytes, content, &opts)?;
+    } else {
+        eh_frame(elf, bytes, sh.sh_addr, content, &opts)?;
+    }
 
     Ok(())
 }
 
 pub fn eh_frame(
     elf: &Elf,
+    bytes: &[u8],
+    vaddr: u64,
+    content: &[u8],
+    opts: &EhArgs,
+) -> Result<()> {
+    let eh = EhFrame::new(content, LittleEndian); // TODO: endianness
+    cfi_entries(elf, bytes, &eh, vaddr, content, opts)
+}
+
+/// Unlike `.eh_frame`, `.debug_frame` uses a CIE ID of `0xffffffff`, plain
+/// offsets for CIE pointers, and absolute addresses in FDEs.
+pub fn debug_frame(
+    elf: &Elf,
+    bytes: &[u8],
+    content: &[u8],
+    opts: &EhArgs,
+) -> Result<()> {
+    let mut debug_frame = DebugFrame::new(content, LittleEndian); // TODO: endianness
+    debug_frame.set_address_size(if elf.is_64 { 8 } else { 4 });
+    cfi_entries(elf, bytes, &debug_frame, 0, content, opts)
+}
+
+fn cfi_entries<'a, S: UnwindSection<EndianSlice<'a, LittleEndian>>>(
+    elf: &Elf,
+    bytes: &[u8],
+    section: &S,
     vaddr: u64,
     content: &[u8],
     opts: &EhArgs,
 ) -> Result<()> {
     let container = elf.header.container().unwrap_or(Container::Big);
     let sp = SizePrint::new(container);
-    let eh = EhFrame::new(content, LittleEndian); // TODO: endianness
 
-    let mut cies = HashMap::new();
     let mut instr_ctx = EhInstrContext {
         cfa_reg: Register(0),
         cfa_off: 0,
         loc: 0,
         data_align: 1,
+        machine: elf.header.e_machine,
         sp,
     };
 
@@ -79,33 +124,42 @@ pub fn eh_frame(
             data: None,
         },
     };
-    let mut entries = eh.entries(&base_addrs);
+    let mut entries = section.entries(&base_addrs);
 
     while let Some(entry) = entries.next()
         .with_context(|| anyhow!("failed to parse entry"))? {
         match entry {
             CieOrFde::Cie(cie) => {
-                print_cie_header(&cie, sp);
-                let mut instr_iter = cie.instructions(&eh, &base_addrs);
+                print_cie_header(&cie, elf, bytes, content);
+                let mut instr_iter = cie.instructions(section, &base_addrs);
                 instr_ctx.data_align = cie.data_alignment_factor();
                 while let Some(instr) = instr_iter.next().unwrap_or(None) {
                     print!("│  ├──⮞ ");
                     instr_ctx.print(instr);
                 }
-                cies.insert(cie.offset(), cie);
             },
             CieOrFde::Fde(fde_unparsed) => {
-                let fde = fde_unparsed.parse(|_, _, offset| {
-                    Ok(cies[&offset.0].clone())
-                }).unwrap();
+                let fde = match fde_unparsed.parse(
+                    |section, bases, offset| section.cie_from_offset(bases, offset)
+                ) {
+                    Ok(fde) => fde,
+                    Err(e) => {
+                        println!("│  │");
+                        println!("│  ├╴ \x1b[93mwarning\x1b[0m: couldn't parse FDE: {e}");
+                        continue;
+                    },
+                };
                 if let Some(addr) = opts.address {
                     if !fde.contains(addr) {
                         continue;
                     }
                 }
                 print_fde_header(&fde, elf);
+                if let Some(lsda) = fde.lsda() {
+                    print_fde_lsda(elf, bytes, lsda, fde.initial_address());
+                }
                 instr_ctx.loc = fde.initial_address();
-                let mut instr_iter = fde.instructions(&eh, &base_addrs);
+                let mut instr_iter = fde.instructions(section, &base_addrs);
                 while let Some(instr) = instr_iter.next().unwrap_or(None) {
                     print!("│  │  ├──⮞ ");
                     instr_ctx.print(instr);
@@ -117,10 +171,57 @@ pub fn eh_frame(
     Ok(())
 }
 
+/// List the PC ranges `(start, length)` of all FDEs, from `.eh_frame` or else
+/// `.debug_frame`. Entries that can't be parsed are ignored.
+pub fn fde_ranges(elf: &Elf, bytes: &[u8]) -> Vec<(u64, u64)> {
+    fn collect<'a, S: UnwindSection<EndianSlice<'a, LittleEndian>>>(
+        section: &S,
+        base_addrs: &BaseAddresses,
+    ) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::new();
+        let mut entries = section.entries(base_addrs);
+        while let Ok(Some(entry)) = entries.next() {
+            if let CieOrFde::Fde(fde) = entry {
+                if let Ok(fde) = fde.parse(
+                    |section, bases, offset| section.cie_from_offset(bases, offset)
+                ) {
+                    ranges.push((fde.initial_address(), fde.len()));
+                }
+            }
+        }
+        ranges
+    }
+
+    let content = |sh: &SectionHeader| bytes.get(sh.file_range()?);
+
+    if let Some(sh) = find_section(elf, ".eh_frame") {
+        let Some(content) = content(sh) else { return Vec::new() };
+        let base_addrs = BaseAddresses {
+            eh_frame_hdr: SectionBaseAddresses::default(),
+            eh_frame: SectionBaseAddresses {
+                section: Some(sh.sh_addr),
+                text: None,
+                data: None,
+            },
+        };
+        collect(&EhFrame::new(content, LittleEndian), &base_addrs)
+    } else if let Some(content) = find_section(elf, ".debug_frame").and_then(content) {
+        let mut debug_frame = DebugFrame::new(content, LittleEndian);
+        debug_frame.set_address_size(if elf.is_64 { 8 } else { 4 });
+        collect(&debug_frame, &BaseAddresses::default())
+    } else {
+        Vec::new()
+    }
+}
+
 fn print_cie_header<R: Reader<Offset = usize>>(
     cie: &CommonInformationEntry<R>,
-    sp: SizePrint
+    elf: &Elf,
+    bytes: &[u8],
+    content: &[u8],
 ) {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
     let table = PairTable(20);
 
     println!("│");
@@ -135,7 +236,34 @@ fn print_cie_header<R: Reader<Offset = usize>>(
 
     print!("│  ├╴");
     table.field("Augmentation");
-    println!();
+    println!("{:?}", cie_augmentation(content, cie.offset()).unwrap_or("???"));
+
+    if let Some((enc, ptr)) = cie.personality_with_encoding() {
+        print!("│  ├╴");
+        table.field("Personality");
+        print!("{}", pointer(elf, bytes, ptr));
+        print!("  \x1b[90m");
+        encoding(enc.0);
+        print!("\x1b[0m");
+    }
+
+    if let Some(enc) = cie.lsda_encoding() {
+        print!("│  ├╴");
+        table.field("LSDA encoding");
+        encoding(enc.0);
+    }
+
+    if let Some(enc) = cie.fde_address_encoding() {
+        print!("│  ├╴");
+        table.field("FDE encoding");
+        encoding(enc.0);
+    }
+
+    if cie.is_signal_trampoline() {
+        print!("│  ├╴");
+        table.field("Signal frame");
+        println!("yes");
+    }
 
     print!("│  ├╴");
     table.field("Code alignment");
@@ -148,7 +276,79 @@ fn print_cie_header<R: Reader<Offset = usize>>(
     print!("│  ├╴");
     table.field("Return addr register");
     println!("{} (%{})", cie.return_address_register().0,
-             register_name(cie.return_address_register()));
+             register_name(elf.header.e_machine, cie.return_address_register()));
+}
+
+/// Read the raw augmentation string of the CIE at `offset`, gimli only gives
+/// us the parsed augmentation data.
+fn cie_augmentation(content: &[u8], offset: usize) -> Option<&str> {
+    let mut d = content.get(offset..)?;
+    let len = d.read_u32::<byteorder::LittleEndian>().ok()?;
+    let id_size = if len == 0xffffffff { 16 } else { 4 };
+    let d = d.get((id_size + 1)..)?;
+    let end = d.iter().position(|&b| b == 0)?;
+
+    std::str::from_utf8(&d[..end]).ok()
+}
+
+fn pointer(elf: &Elf, bytes: &[u8], ptr: Pointer) -> String {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+
+    match ptr {
+        Pointer::Direct(addr) => match sym_name_at(elf, addr) {
+            Some(name) => format!("{} ({name})", sp.hex(addr)),
+            None => sp.hex(addr).to_string(),
+        },
+        Pointer::Indirect(addr) => {
+            let name = sym_name_at(elf, addr)
+                .map(|n| n.trim_start_matches("DW.ref."))
+                .or_else(|| {
+                    let d = content_at_vaddr(elf, bytes, addr)?;
+                    let target = read_encoded(
+                        elf, bytes, 0x00, d, addr, &PtrBases::default(),
+                    ).ok()?.2;
+                    sym_name_at(elf, target)
+                });
+            match name {
+                Some(name) => format!("*{} ({name})", sp.hex(addr)),
+                None => format!("*{}", sp.hex(addr)),
+            }
+        },
+    }
+}
+
+fn print_fde_lsda(elf: &Elf, bytes: &[u8], lsda: Pointer, func_start: u64) {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+    let table = PairTable(10);
+
+    print!("│  │  ├╴");
+    table.field("LSDA");
+    let addr = match lsda {
+        Pointer::Direct(addr) => addr,
+        Pointer::Indirect(ptr) => match read_ptr(elf, bytes, ptr) {
+            Some(addr) => addr,
+            None => {
+                println!("\x1b[93mcouldn't read the indirect pointer at {}\x1b[0m", sp.hex(ptr));
+                return;
+            },
+        },
+    };
+    let section = elf.section_headers.iter()
+        .find(|sh| sh.is_alloc() && sh.vm_range().contains(&(addr as usize)))
+        .and_then(|sh| Some((elf.shdr_strtab.get_at(sh.sh_name)?, sh.sh_addr)));
+    match section {
+        Some((name, sh_addr)) => {
+            println!("{} ({name} + {:#x})", sp.hex(addr), addr - sh_addr);
+        },
+        None => println!("{}", sp.hex(addr)),
+    }
+
+    match parse_lsda(elf, bytes, addr, func_start) {
+        Ok(lsda) => print_lsda(elf, bytes, &lsda, "│  │  │  "),
+        Err(e) => println!("│  │  │  └╴ \x1b[93mwarning\x1b[0m: {e:#}"),
+    }
 }
 
 fn print_fde_header<R: Reader<Offset = usize>>(
@@ -186,10 +386,15 @@ pub struct EhInstrContext {
     pub cfa_off: u64,
     pub loc: u64,
     pub data_align: i64,
+    pub machine: u16,
     pub sp: SizePrint,
 }
 
 impl EhInstrContext {
+    fn reg_name(&self, r: Register) -> &'static str {
+        register_name(self.machine, r)
+    }
+
     pub fn print<R: Reader>(&mut self, instr: CallFrameInstruction<R>) {
         use CallFrameInstruction::*;
 
@@ -212,7 +417,7 @@ impl EhInstrContext {
                 println!(
                     "{:30} cfa = %{} + {offset}",
                     format!("DW_CFA_def_cfa({}, {offset})", register.0),
-                    register_name(register),
+                    self.reg_name(register),
                 );
                 self.cfa_reg = register;
                 self.cfa_off = offset;
@@ -226,7 +431,7 @@ impl EhInstrContext {
                 println!(
                     "{:30} cfa = %{} + \x1b[90m{}\x1b[0m",
                     format!("DW_CFA_def_cfa_register({})", register.0),
-                    register_name(register), self.cfa_off,
+                    self.reg_name(register), self.cfa_off,
                 );
                 self.cfa_reg = register;
             },
@@ -234,7 +439,7 @@ impl EhInstrContext {
                 println!(
                     "{:30} cfa = \x1b[90m%{}\x1b[0m + {offset}",
                     format!("DW_CFA_def_cfa_offset({offset})"),
-                    register_name(self.cfa_reg),
+                    self.reg_name(self.cfa_reg),
                 );
                 self.cfa_off = offset;
             },
@@ -248,14 +453,14 @@ impl EhInstrContext {
                 println!(
                     "{:30} %{} @ ??? (unrecoverable)",
                     format!("DW_CFA_undefined({})", register.0),
-                    register_name(register),
+                    self.reg_name(register),
                 );
             },
             SameValue { register } => {
                 println!(
                     "{:30} %{} untouched",
                     format!("DW_CFA_same_value({})", register.0),
-                    register_name(register),
+                    self.reg_name(register),
                 );
             },
             Offset { register, factored_offset } => {
@@ -263,7 +468,7 @@ impl EhInstrContext {
                 println!(
                     "{:30} %{} @ cfa {} {}",
                     format!("DW_CFA_offset({}, {factored_offset})", register.0),
-                    register_name(register),
+                    self.reg_name(register),
                     if off < 0 { "−" } else { "+" },
                     off.abs(),
                 );
@@ -287,14 +492,14 @@ impl EhInstrContext {
                 println!(
                     "{:30} %{} = %{}",
                     format!("DW_CFA_register({}, {})", dest_register.0, src_register.0),
-                    register_name(dest_register), register_name(src_register),
+                    self.reg_name(dest_register), self.reg_name(src_register),
                 );
             },
             Expression { register, expression } => {
                 println!(
                     "DW_CFA_expression({}, {:02x?})\t\t%{} = ...",
                     register.0, expression.0.to_slice().unwrap(),
-                    register_name(register),
+                    self.reg_name(register),
                 );
             },
             ValExpression { register, expression } => {
@@ -307,7 +512,7 @@ impl EhInstrContext {
                 println!(
                     "{:30} %{} @ (initial rule)",
                     format!("DW_CFA_restore({})", register.0),
-                    register_name(register),
+                    self.reg_name(register),
                 );
             },
             RememberState => println!("DW_CFA_remember_state()"),
@@ -318,10 +523,23 @@ impl EhInstrContext {
     }
 }
 
-pub fn eh_frame_hdr(elf: &Elf, pc: u64, content: &[u8]) {
+/// The pointer encoding of absent values.
+pub const DW_EH_PE_OMIT: u8 = 0xff;
+
+pub fn eh_frame_hdr(
+    elf: &Elf,
+    bytes: &[u8],
+    pc: u64,
+    content: &[u8],
+) -> Result<()> {
     let container = elf.header.container().unwrap_or(Container::Big);
     let sp = SizePrint::new(container);
 
+    if content.len() < 4 {
+        return Err(anyhow!("`.eh_frame_hdr` is too short ({} B)", content.len()));
+    }
+    let bases = eh_frame_hdr_bases(elf, pc);
+
     println!("\x1b[1;96m─── Header ───\x1b[0m");
     let table = PairTable(22);
 
@@ -339,62 +557,316 @@ pub fn eh_frame_hdr(elf: &Elf, pc: u64, content: &[u8]) {
 
     let mut off = 4;
     table.field(".eh_frame pointer");
-    let (size, val) = value(content[1], &content[off..]);
-    match val {
-        Value::Signed(n) => print!("{n}"),
-        Value::Unsigned(n) => print!("{n}"),
-    }
-    println!("  (-> {})", sp.hex(value_abs(content[1], val, pc + off as u64, pc)));
+    let (size, val, abs) = read_encoded(
+        elf, bytes, content[1], &content[off..], pc.wrapping_add(off as u64), &bases,
+    ).context("couldn't decode eh_frame_ptr")?;
+    println!("{val}  (-> {})", sp.hex(abs));
     off += size;
 
+    // `DW_EH_PE_omit` for the count or the table means there is no search
+    // table at all.
     table.field("Nr entries");
-    let (size, val) = value(content[2], &content[off..]);
-    let nr_entries;
-    match val {
-        Value::Signed(n) => { nr_entries = n as usize; println!("{n}"); },
-        Value::Unsigned(n) => { nr_entries = n as usize; println!("{n}"); },
+    if content[2] == DW_EH_PE_OMIT || content[3] == DW_EH_PE_OMIT {
+        println!("\x1b[90mno search table\x1b[0m");
+        return Ok(());
     }
+    let (size, val, nr_entries) = read_encoded(
+        elf, bytes, content[2], &content[off..], pc.wrapping_add(off as u64), &bases,
+    ).context("couldn't decode fde_count")?;
+    println!("{val}");
     off += size;
 
     println!("\n\x1b[1;96m─── Table content ───\x1b[0m");
 
-    for _ in 0..nr_entries {
-        print!("\t");
+    for i in 0..nr_entries {
+        let mut entry = || -> Result<_> {
+            let (size, loc_val, loc) = read_encoded(
+                elf, bytes, content[3], content.get(off..).unwrap_or(&[]),
+                pc.wrapping_add(off as u64), &bases,
+            )?;
+            off += size;
+            let (size, fde_val, fde) = read_encoded(
+                elf, bytes, content[3], content.get(off..).unwrap_or(&[]),
+                pc.wrapping_add(off as u64), &bases,
+            )?;
+            off += size;
+            Ok((loc_val, loc, fde_val, fde))
+        };
+
+        match entry() {
+            Ok((loc_val, loc, fde_val, fde)) => println!(
+                "\t\x1b[90m({loc_val:>10})\x1b[0m  {}  ->  {}  \x1b[90m({fde_val:>10})\x1b[0m",
+                sp.hex(loc), sp.hex(fde),
+            ),
+            Err(e) => {
+                println!("\t\x1b[93mwarning\x1b[0m: couldn't decode entry #{i}: {e:#}");
+                break;
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify that `.eh_frame_hdr`'s binary search table is consistent with the
+/// FDEs in `.eh_frame`, and that every function in the executable sections is
+/// covered by an FDE.
+pub fn check_eh_frame_hdr(
+    elf: &Elf,
+    bytes: &[u8],
+    eh_vaddr: u64,
+    eh_content: &[u8],
+) -> Result<()> {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+    let mut nr_errors = 0;
+    let mut nr_warnings = 0;
+
+    let mut error = |msg: String| {
+        println!("\x1b[31m✗\x1b[0m {msg}");
+        nr_errors += 1;
+    };
+
+    print_header("EH_FRAME_HDR CHECK");
+
+    let eh = EhFrame::new(eh_content, LittleEndian);
+    let base_addrs = BaseAddresses {
+        eh_frame_hdr: SectionBaseAddresses::default(),
+        eh_frame: SectionBaseAddresses {
+            section: Some(eh_vaddr),
+            text: None,
+            data: None,
+        },
+    };
+
+    let mut cies = HashMap::new();
+    let mut fdes = BTreeMap::new();
+    let mut entries = eh.entries(&base_addrs);
+    while let Some(entry) = entries.next()
+        .with_context(|| anyhow!("failed to parse entry"))? {
+        match entry {
+            CieOrFde::Cie(cie) => {
+                cies.insert(cie.offset(), cie);
+            },
+            CieOrFde::Fde(fde_unparsed) => {
+                match fde_unparsed.parse(|_, _, off| {
+                    cies.get(&off.0).cloned().ok_or(gimli::Error::NoEntryAtGivenOffset)
+                }) {
+                    Ok(fde) => {
+                        fdes.insert(fde.offset(), (fde.initial_address(), fde.len()));
+                    },
+                    Err(e) => error(format!("an FDE couldn't be parsed: {e}")),
+                }
+            },
+        }
+    }
+    println!("  Found {} CIEs and {} FDEs in the frame section", cies.len(), fdes.len());
+
+    let hdr = find_section(elf, ".eh_frame_hdr").map(|hdr_sh| {
+        let content = hdr_sh.file_range()
+            .and_then(|r| bytes.get(r))
+            .ok_or_else(|| anyhow!("`.eh_frame_hdr` has no content"))?;
+        parse_eh_frame_hdr(elf, bytes, hdr_sh.sh_addr, content)
+    }).transpose()?;
 
-        let (size, val) = value(content[3], &content[off..]);
-        print!("\x1b[90m(");
-        match val {
-            Value::Signed(n) => print!("{n:10}"),
-            Value::Unsigned(n) => print!("{n:10}"),
+    if let Some(ref hdr) = hdr {
+        if hdr.eh_frame_ptr != eh_vaddr {
+            error(format!(
+                "eh_frame_ptr is {} but the frame section is at {}",
+                sp.hex(hdr.eh_frame_ptr), sp.hex(eh_vaddr),
+            ));
         }
-        off += size;
-        print!(")\x1b[0m");
-
-        print!("  {}", sp.hex(value_abs(content[3], val, pc + off as u64, pc)));
-        print!("  ->  ");
-
-        let (size, val) = value(content[3], &content[off..]);
-        print!("{}  ", sp.hex(value_abs(content[3], val, pc + off as u64, pc)));
-        print!("\x1b[90m(");
-        match val {
-            Value::Signed(n) => print!("{n:10}"),
-            Value::Unsigned(n) => print!("{n:10}"),
+    }
+
+    if let Some(table) = hdr.as_ref().and_then(|hdr| hdr.table.as_ref()) {
+        if table.len() != fdes.len() {
+            error(format!(
+                "table has {} entries but there are {} FDEs",
+                table.len(), fdes.len(),
+            ));
         }
-        off += size;
-        print!(")\x1b[0m");
 
-        println!();
+        let mut sorted = true;
+        for (i, w) in table.windows(2).enumerate() {
+            if w[0].0 >= w[1].0 {
+                error(format!(
+                    "table entry #{} ({}) is not sorted after entry #{i} ({})",
+                    i + 1, sp.hex(w[1].0), sp.hex(w[0].0),
+                ));
+                sorted = false;
+            }
+        }
+        if sorted {
+            println!("\x1b[32m✓\x1b[0m Table of {} entries is sorted", table.len());
+        }
+
+        let mut covered = HashSet::new();
+        let mut nr_valid = 0;
+        for (i, &(loc, fde_addr)) in table.iter().enumerate() {
+            let fde = fde_addr.checked_sub(eh_vaddr)
+                .and_then(|off| fdes.get_key_value(&(off as usize)));
+            match fde {
+                None => error(format!(
+                    "table entry #{i} ({}) points to {} which is not an FDE",
+                    sp.hex(loc), sp.hex(fde_addr),
+                )),
+                Some((&off, &(initial, _))) if initial != loc => {
+                    covered.insert(off);
+                    error(format!(
+                        "table entry #{i} has location {} but its FDE starts at {}",
+                        sp.hex(loc), sp.hex(initial),
+                    ));
+                },
+                Some((&off, _)) => {
+                    covered.insert(off);
+                    nr_valid += 1;
+                },
+            }
+        }
+        if nr_valid == table.len() {
+            println!("\x1b[32m✓\x1b[0m All table entries point to a matching FDE");
+        }
+
+        let mut all_covered = true;
+        for (off, &(initial, len)) in &fdes {
+            if !covered.contains(off) {
+                error(format!(
+                    "FDE at offset {off:#x} for {}..{} is missing from the table",
+                    sp.hex(initial), sp.hex(initial + len),
+                ));
+                all_covered = false;
+            }
+        }
+        if all_covered {
+            println!("\x1b[32m✓\x1b[0m All FDEs are referenced by the table");
+        }
+    } else if hdr.is_some() {
+        println!("\x1b[93m!\x1b[0m `.eh_frame_hdr` has no search table, skipping table checks");
+    } else {
+        println!("\x1b[93m!\x1b[0m No `.eh_frame_hdr` section, skipping table checks");
+    }
+
+    // The address ranges covered by FDEs, sorted and merged so that a function
+    // is looked up with a binary search.
+    let mut ranges: Vec<(u64, u64)> = fdes.values()
+        .map(|&(start, len)| (start, start.saturating_add(len)))
+        .collect();
+    ranges.sort_unstable();
+    ranges.dedup_by(|next, prev| {
+        if next.0 <= prev.1 {
+            prev.1 = prev.1.max(next.1);
+            true
+        } else {
+            false
+        }
+    });
+
+    let mut nr_missing = 0;
+    for sym in elf.syms.iter() {
+        if sym.st_type() != STT_FUNC || sym.st_value == 0 {
+            continue;
+        }
+        let in_text = elf.section_headers.get(sym.st_shndx)
+            .map(|sh| sh.is_executable())
+            .unwrap_or(false);
+        if !in_text {
+            continue;
+        }
+        let idx = ranges.partition_point(|&(start, _)| start <= sym.st_value);
+        let has_fde = idx > 0 && sym.st_value < ranges[idx - 1].1;
+        if !has_fde {
+            let name = elf.strtab.get_at(sym.st_name).unwrap_or("???");
+            println!(
+                "\x1b[93m!\x1b[0m Function {} at {} has no FDE",
+                demangle(name), sp.hex(sym.st_value),
+            );
+            nr_missing += 1;
+        }
+    }
+    nr_warnings += nr_missing;
+    if nr_missing == 0 {
+        println!("\x1b[32m✓\x1b[0m All functions are covered by an FDE");
+    }
+
+    println!();
+    if nr_errors > 0 {
+        bail!("found {nr_errors} error(s) and {nr_warnings} warning(s)");
+    }
+    println!("No errors, {nr_warnings} warning(s)");
+
+    Ok(())
+}
+
+pub struct EhFrameHdr {
+    pub eh_frame_ptr: u64,
+    /// The binary search table, absent when its encoding is `DW_EH_PE_omit`.
+    pub table: Option<Vec<(u64, u64)>>,
+}
+
+pub fn parse_eh_frame_hdr(
+    elf: &Elf,
+    bytes: &[u8],
+    pc: u64,
+    content: &[u8],
+) -> Result<EhFrameHdr> {
+    if content.len() < 4 {
+        bail!("`.eh_frame_hdr` is too short");
+    }
+    if content[0] != 1 {
+        bail!("unsupported `.eh_frame_hdr` version {}", content[0]);
+    }
+    let bases = eh_frame_hdr_bases(elf, pc);
+    let read = |enc: u8, off: &mut usize| -> Result<u64> {
+        let (size, _, abs) = read_encoded(
+            elf, bytes, enc, content.get(*off..).unwrap_or(&[]),
+            pc.wrapping_add(*off as u64), &bases,
+        )?;
+        *off += size;
+        Ok(abs)
+    };
+
+    let mut off = 4;
+    let eh_frame_ptr = read(content[1], &mut off)
+        .context("couldn't decode eh_frame_ptr")?;
+    if content[2] == DW_EH_PE_OMIT || content[3] == DW_EH_PE_OMIT {
+        return Ok(EhFrameHdr { eh_frame_ptr, table: None });
+    }
+    let nr_entries = read(content[2], &mut off)
+        .context("couldn't decode fde_count")?;
+
+    let mut table = Vec::new();
+    for i in 0..nr_entries {
+        let loc = read(content[3], &mut off)
+            .with_context(|| format!("couldn't decode table entry #{i}"))?;
+        let fde = read(content[3], &mut off)
+            .with_context(|| format!("couldn't decode table entry #{i}"))?;
+        table.push((loc, fde));
+    }
+
+    Ok(EhFrameHdr { eh_frame_ptr, table: Some(table) })
+}
+
+/// In `.eh_frame_hdr`, `DW_EH_PE_datarel` is relative to the start of the
+/// section itself.
+fn eh_frame_hdr_bases(elf: &Elf, hdr_addr: u64) -> PtrBases {
+    PtrBases {
+        text: find_section(elf, ".text").map(|sh| sh.sh_addr),
+        data: Some(hdr_addr),
+        func: None,
     }
 }
 
 fn encoding(n: u8) {
-    print!("{n:#04x} ");
-    if n == 0xff {
-        println!("(no value)");
-        return;
+    println!("{n:#04x} {}", encoding_name(n));
+}
+
+pub fn encoding_name(n: u8) -> String {
+    if n == DW_EH_PE_OMIT {
+        return "(no value)".to_string();
     }
 
     let size = match n & 0x0f {
+        0x00 => "native size",
         0x01 => "unsigned LEB128",
         0x02 => "u16",
         0x03 => "u32",
@@ -405,52 +877,214 @@ fn encoding(n: u8) {
         0x0c => "i64",
         _ => "???",
     };
-    let app = match n & 0xf0 {
+    let app = match n & 0x70 {
         0x00 => "as is",
         0x10 => "relative to program counter",
+        0x20 => "relative to .text",
         0x30 => "relative to .eh_frame_hdr start",
+        0x40 => "relative to function start",
+        0x50 => "aligned",
         _ => "???",
     };
 
-    println!("({size}, {app})");
+    if n & 0x80 != 0 {
+        format!("({size}, {app}, indirect)")
+    } else {
+        format!("({size}, {app})")
+    }
+}
+
+/// Base addresses for the `DW_EH_PE_textrel`, `DW_EH_PE_datarel`, and
+/// `DW_EH_PE_funcrel` pointer applications.
+#[derive(Default, Copy, Clone)]
+pub struct PtrBases {
+    pub text: Option<u64>,
+    pub data: Option<u64>,
+    pub func: Option<u64>,
 }
 
-fn value(enc: u8, mut d: &[u8]) -> (usize, Value) {
-    use byteorder::LittleEndian;
+/// Decode a `DW_EH_PE_*` encoded pointer located at virtual address `vaddr`,
+/// `d` being the content starting at that address. Returns the number of bytes
+/// read, the raw value, and the resolved address; indirect pointers are
+/// dereferenced by reading the ELF's loaded segments.
+pub fn read_encoded(
+    elf: &Elf,
+    bytes: &[u8],
+    enc: u8,
+    d: &[u8],
+    vaddr: u64,
+    bases: &PtrBases,
+) -> Result<(usize, Value, u64)> {
+    if enc == DW_EH_PE_OMIT {
+        bail!("pointer is omitted (DW_EH_PE_omit)");
+    }
 
-    match enc & 0x0f {
-        0x01 => unimplemented!("unsigned LEB128"), // TODO: implement
-        0x02 => (2, Value::Unsigned(d.read_u16::<LittleEndian>().unwrap() as u64)),
-        0x03 => (4, Value::Unsigned(d.read_u32::<LittleEndian>().unwrap() as u64)),
-        0x04 => (8, Value::Unsigned(d.read_u64::<LittleEndian>().unwrap())),
-        0x09 => unimplemented!("signed LEB128"), // TODO: implement
-        0x0a => (2, Value::Signed(d.read_i16::<LittleEndian>().unwrap() as i64)),
-        0x0b => (4, Value::Signed(d.read_i32::<LittleEndian>().unwrap() as i64)),
-        0x0c => (8, Value::Signed(d.read_i64::<LittleEndian>().unwrap())),
-        _ => panic!("invalid encoding"), // TODO: don't panic
+    let mut skip = 0;
+    if enc & 0x70 == 0x50 {
+        let align = if elf.is_64 { 8 } else { 4 };
+        skip = ((align - vaddr % align) % align) as usize;
     }
+    let d = d.get(skip..)
+        .ok_or_else(|| anyhow!("unexpected end of data"))?;
+
+    let (size, val) = value(elf, enc, d)?;
+    let addr = value_abs(elf, bytes, enc, val, vaddr.wrapping_add(skip as u64), bases)?;
+
+    Ok((skip + size, val, addr))
 }
 
-fn value_abs(enc: u8, v: Value, pc: u64, ehhdr: u64) -> u64 {
-    let base = match enc & 0xf0 {
-        0x00 => 0,
+fn value(elf: &Elf, enc: u8, d: &[u8]) -> Result<(usize, Value)> {
+    use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+    let (size, signed) = match enc & 0x0f {
+        0x00 => (if elf.is_64 { 8 } else { 4 }, false),
+        0x01 => {
+            let mut r = EndianSlice::new(d, gimli::LittleEndian);
+            let n = gimli::leb128::read::unsigned(&mut r)
+                .map_err(|_| anyhow!("invalid unsigned LEB128"))?;
+            return Ok((d.len() - r.len(), Value::Unsigned(n)));
+        },
+        0x02 => (2, false),
+        0x03 => (4, false),
+        0x04 => (8, false),
+        0x09 => {
+            let mut r = EndianSlice::new(d, gimli::LittleEndian);
+            let n = gimli::leb128::read::signed(&mut r)
+                .map_err(|_| anyhow!("invalid signed LEB128"))?;
+            return Ok((d.len() - r.len(), Value::Signed(n)));
+        },
+        0x0a => (2, true),
+        0x0b => (4, true),
+        0x0c => (8, true),
+        f => bail!("invalid pointer format {f:#x} in encoding {enc:#04x}"),
+    };
+
+    let d = d.get(..size)
+        .ok_or_else(|| anyhow!("unexpected end of data"))?;
+    let val = match (signed, elf.little_endian) {
+        (false, true) => Value::Unsigned(LittleEndian::read_uint(d, size)),
+        (false, false) => Value::Unsigned(BigEndian::read_uint(d, size)),
+        (true, true) => Value::Signed(LittleEndian::read_int(d, size)),
+        (true, false) => Value::Signed(BigEndian::read_int(d, size)),
+    };
+
+    Ok((size, val))
+}
+
+fn value_abs(
+    elf: &Elf,
+    bytes: &[u8],
+    enc: u8,
+    v: Value,
+    pc: u64,
+    bases: &PtrBases,
+) -> Result<u64> {
+    let base = match enc & 0x70 {
+        0x00 | 0x50 => 0,
         0x10 => pc,
-        0x30 => ehhdr,
-        _ => panic!("invalid encoding"), // TODO: don't panic
+        0x20 => bases.text
+            .ok_or_else(|| anyhow!("no text base for DW_EH_PE_textrel"))?,
+        0x30 => bases.data
+            .ok_or_else(|| anyhow!("no data base for DW_EH_PE_datarel"))?,
+        0x40 => bases.func
+            .ok_or_else(|| anyhow!("no function base for DW_EH_PE_funcrel"))?,
+        app => bail!("invalid pointer application {app:#x} in encoding {enc:#04x}"),
+    };
+
+    let addr = match v {
+        Value::Signed(n) => base.wrapping_add(n as u64),
+        Value::Unsigned(n) => base.wrapping_add(n),
     };
 
-    match v {
-        Value::Signed(n) => (base as i64 + n) as u64,
-        Value::Unsigned(n) => base + n,
+    if enc & 0x80 != 0 {
+        let d = content_at_vaddr(elf, bytes, addr).ok_or_else(|| anyhow!(
+            "indirect pointer to {addr:#x} which is not in a loaded segment"
+        ))?;
+        let (_, ptr) = value(elf, 0x00, d)?;
+        match ptr {
+            Value::Signed(n) => Ok(n as u64),
+            Value::Unsigned(n) => Ok(n),
+        }
+    } else {
+        Ok(addr)
     }
 }
 
 #[derive(Copy, Clone)]
-enum Value {
+pub enum Value {
     Signed(i64),
     Unsigned(u64),
 }
 
-fn register_name(r: Register) -> &'static str {
-    X86_64::register_name(r).unwrap_or("???") // TODO: handle other archs
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Signed(n) => n.fmt(f),
+            Value::Unsigned(n) => n.fmt(f),
+        }
+    }
+}
+
+fn register_name(machine: u16, r: Register) -> &'static str {
+    use goblin::elf::header::*;
+
+    let name = match machine {
+        EM_386 => X86::register_name(r),
+        EM_X86_64 => X86_64::register_name(r),
+        EM_ARM => Arm::register_name(r),
+        EM_AARCH64 => AArch64::register_name(r),
+        EM_RISCV => RiscV::register_name(r),
+        EM_PPC | EM_PPC64 => PPC_REGS.get(r.0 as usize).copied(),
+        EM_S390 => S390_REGS.get(r.0 as usize).copied(),
+        EM_MIPS => MIPS_REGS.get(r.0 as usize).copied(),
+        _ => None,
+    };
+
+    name.unwrap_or("???")
 }
+
+/// DWARF register numbers for PowerPC, as emitted by GCC in `.eh_frame`.
+const PPC_REGS: [&str; 111] = [
+    "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+    "r16", "r17", "r18", "r19", "r20", "r21", "r22", "r23",
+    "r24", "r25", "r26", "r27", "r28", "r29", "r30", "r31",
+    "f0", "f1", "f2", "f3", "f4", "f5", "f6", "f7",
+    "f8", "f9", "f10", "f11", "f12", "f13", "f14", "f15",
+    "f16", "f17", "f18", "f19", "f20", "f21", "f22", "f23",
+    "f24", "f25", "f26", "f27", "f28", "f29", "f30", "f31",
+    "mq", "lr", "ctr", "ap",
+    "cr0", "cr1", "cr2", "cr3", "cr4", "cr5", "cr6", "cr7",
+    "xer",
+    "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7",
+    "v8", "v9", "v10", "v11", "v12", "v13", "v14", "v15",
+    "v16", "v17", "v18", "v19", "v20", "v21", "v22", "v23",
+    "v24", "v25", "v26", "v27", "v28", "v29", "v30", "v31",
+    "vrsave", "vscr",
+];
+
+/// DWARF register numbers for s390x, from the zSeries ELF ABI supplement.
+const S390_REGS: [&str; 66] = [
+    "r0", "r1", "r2", "r3", "r4", "r5", "r6", "r7",
+    "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+    "f0", "f2", "f4", "f6", "f1", "f3", "f5", "f7",
+    "f8", "f10", "f12", "f14", "f9", "f11", "f13", "f15",
+    "c0", "c1", "c2", "c3", "c4", "c5", "c6", "c7",
+    "c8", "c9", "c10", "c11", "c12", "c13", "c14", "c15",
+    "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7",
+    "a8", "a9", "a10", "a11", "a12", "a13", "a14", "a15",
+    "pswm", "pswa",
+];
+
+/// DWARF register numbers for MIPS.
+const MIPS_REGS: [&str; 66] = [
+    "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3",
+    "t0", "t1", "t2", "t3", "t4", "t5", "t6", "t7",
+    "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7",
+    "t8", "t9", "k0", "k1", "gp", "sp", "fp", "ra",
+    "f0", "f1", "f2", "f3", "f4", "f5", "f6", "f7",
+    "f8", "f9", "f10", "f11", "f12", "f13", "f14", "f15",
+    "f16", "f17", "f18", "f19", "f20", "f21", "f22", "f23",
+    "f24", "f25", "f26", "f27", "f28", "f29", "f30", "f31",
+    "hi", "lo",
+];
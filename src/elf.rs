@@ -8,17 +8,22 @@
  * any later version. See LICENSE file for more information.                  *
  ******************************************************************************/
 
+use anyhow::{Context, Result};
 use goblin::elf::{Elf, ProgramHeader, Sym, Symtab};
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::section_header::SHN_UNDEF;
+use goblin::elf::sym::{STT_FILE, STT_FUNC, STT_GNU_IFUNC, STT_SECTION};
 use goblin::strtab::Strtab;
-use rustc_demangle::demangle;
+use regex::Regex;
+
+use crate::demangle::demangle;
 
 pub fn symbol_file_offset(elf: &Elf, sym_name: &str) -> Option<u64> {
-    // TODO: handle stripped binaries
     let sym = find_symbol(&elf.syms, &elf.strtab, sym_name)
         .or_else(|| find_symbol(&elf.dynsyms, &elf.dynstrtab, sym_name))?;
     let ph = ph_by_vaddr(elf, sym.st_value)?;
 
-    Some(ph.p_offset + (sym.st_value - ph.p_vaddr))
+    ph.p_offset.checked_add(sym.st_value - ph.p_vaddr)
 }
 
 pub fn find_symbol(tab: &Symtab, strtab: &Strtab, name: &str) -> Option<Sym> {
@@ -28,11 +33,82 @@ pub fn find_symbol(tab: &Symtab, strtab: &Strtab, name: &str) -> Option<Sym> {
         })
         .or_else(|| tab.iter().find(|sym| {
             strtab.get_at(sym.st_name)
-                .map(|n| demangle(n).to_string() == name)
+                .map(|n| is_demangled_name(n, name))
                 .unwrap_or(false)
         }))
 }
 
+/// Whether `name` is the demangled `raw` name, with or without Rust's hash.
+fn is_demangled_name(raw: &str, name: &str) -> bool {
+    let demangled = demangle(raw);
+    demangled.to_string() == name || format!("{demangled:#}") == name
+}
+
+/// Find every symbol `pattern` may designate, with its raw name. Symbols named
+/// exactly `pattern` are preferred; otherwise, the defined symbols whose name
+/// contains `pattern`, or matches it as a regular expression if `regex` is
+/// set, are returned. Demangled names are matched without Rust's hash. The
+/// symbol table is searched first, then the dynamic symbol table.
+///
+/// With `functions`, only functions are searched for by partial name, and they
+/// are preferred over other symbols with the exact name.
+pub fn match_symbols<'a>(
+    elf: &'a Elf,
+    pattern: &str,
+    regex: bool,
+    functions: bool,
+) -> Result<Vec<(Sym, &'a str)>> {
+    let re = regex.then(|| Regex::new(pattern))
+        .transpose()
+        .with_context(|| format!("invalid regular expression {pattern:?}"))?;
+    let is_match = |raw: &str| {
+        let demangled = format!("{:#}", demangle(raw));
+        match re {
+            Some(ref re) => re.is_match(raw) || re.is_match(&demangled),
+            None => raw.contains(pattern) || demangled.contains(pattern),
+        }
+    };
+
+    let is_fn = |sym: &Sym| {
+        matches!(sym.st_type(), STT_FUNC | STT_GNU_IFUNC)
+            || (sym.st_size > 0 && elf.section_headers.get(sym.st_shndx)
+                .is_some_and(|sh| sh.is_executable()))
+    };
+
+    for (tab, strtab) in [(&elf.syms, &elf.strtab), (&elf.dynsyms, &elf.dynstrtab)] {
+        let named = || tab.iter()
+            .filter_map(|sym| Some((sym, strtab.get_at(sym.st_name)?)))
+            .filter(|(_, name)| !name.is_empty());
+
+        let mut matches: Vec<_> = if regex {
+            Vec::new()
+        } else {
+            named().filter(|&(_, n)| n == pattern || is_demangled_name(n, pattern)).collect()
+        };
+        if functions && matches.iter().any(|(sym, _)| is_fn(sym)) {
+            matches.retain(|(sym, _)| is_fn(sym));
+        }
+        if matches.is_empty() {
+            matches = named()
+                .filter(|(sym, _)| sym.st_shndx != SHN_UNDEF as usize
+                    && sym.st_type() != STT_FILE
+                    && sym.st_type() != STT_SECTION
+                    && (!functions || is_fn(sym)))
+                .filter(|&(_, n)| is_match(n))
+                .collect();
+        }
+
+        // The same symbol may be listed more than once.
+        matches.sort_by_key(|&(sym, name)| (sym.st_value, name));
+        matches.dedup_by_key(|&mut (sym, name)| (sym.st_value, name));
+        if !matches.is_empty() {
+            return Ok(matches);
+        }
+    }
+
+    Ok(Vec::new())
+}
+
 pub fn find_symbol_by_addr(tab: &Symtab, addr: u64) -> Option<Sym> {
     tab.iter()
         .find(|sym| {
@@ -40,7 +116,30 @@ pub fn find_symbol_by_addr(tab: &Symtab, addr: u64) -> Option<Sym> {
         })
 }
 
+/// The loadable segment containing `vaddr`.
 pub fn ph_by_vaddr<'a>(elf: &'a Elf, vaddr: u64) -> Option<&'a ProgramHeader> {
     elf.program_headers.iter()
-        .find(|&ph| (ph.p_vaddr..(ph.p_vaddr + ph.p_memsz)).contains(&vaddr))
+        .filter(|ph| ph.p_type == PT_LOAD)
+        .find(|&ph| vaddr.checked_sub(ph.p_vaddr).is_some_and(|off| off < ph.p_memsz))
+}
+
+pub fn vaddr_file_offset(elf: &Elf, vaddr: u64) -> Option<u64> {
+    let ph = ph_by_vaddr(elf, vaddr)?;
+    let off = vaddr - ph.p_vaddr;
+
+    ph.p_offset.checked_add(off).filter(|_| off < ph.p_filesz)
+}
+
+/// Returns the file content at virtual address `vaddr`, up to the end of the
+/// segment's in-file data.
+pub fn content_at_vaddr<'a>(
+    elf: &Elf,
+    bytes: &'a [u8],
+    vaddr: u64,
+) -> Option<&'a [u8]> {
+    let ph = ph_by_vaddr(elf, vaddr)?;
+    let start = vaddr_file_offset(elf, vaddr)? as usize;
+    let end = ph.p_offset.checked_add(ph.p_filesz)? as usize;
+
+    bytes.get(start..end)
 }
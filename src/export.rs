@@ -0,0 +1,280 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Export of the loadable segments as a flat binary image, Intel HEX, or
+//! Motorola S-records.
+
+use std::fmt::Write;
+use anyhow::{anyhow, bail, Context, Result};
+use goblin::container::Container;
+use goblin::elf::Elf;
+use goblin::elf::program_header::PT_LOAD;
+
+use crate::args::{ExportArgs, ExportFormat};
+use crate::print::{BinSize, print_header, SizePrint};
+
+/// Bytes per data record in Intel HEX and S-record files.
+const RECORD_LEN: usize = 16;
+/// Flat images are refused past this size: segment addresses of crafted ELFs
+/// may be far apart.
+const MAX_IMAGE_SIZE: u64 = 1 << 30;
+
+/// Contiguous bytes to load at an address.
+struct Chunk<'a> {
+    addr: u64,
+    data: std::borrow::Cow<'a, [u8]>,
+}
+
+pub fn export(elf: &Elf, bytes: &[u8], args: &ExportArgs) -> Result<()> {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+
+    let mut segments: Vec<(usize, Chunk)> = elf.program_headers.iter()
+        .enumerate()
+        .filter(|(_, ph)| ph.p_type == PT_LOAD && ph.p_filesz > 0)
+        .map(|(i, ph)| {
+            let data = bytes.get(ph.file_range())
+                .ok_or_else(|| anyhow!("segment #{i} goes past the end of the file"))?;
+            let addr = if args.r#virtual { ph.p_vaddr } else { ph.p_paddr };
+            if addr.checked_add(data.len() as u64).is_none() {
+                bail!("segment #{i} goes past the end of the address space");
+            }
+            Ok((i, Chunk { addr, data: data.into() }))
+        })
+        .collect::<Result<_>>()?;
+    if segments.is_empty() {
+        bail!("the ELF has no loadable segment with content");
+    }
+    segments.sort_by_key(|(_, chunk)| chunk.addr);
+
+    for pair in segments.windows(2) {
+        let ((i, a), (j, b)) = (&pair[0], &pair[1]);
+        if a.addr + a.data.len() as u64 > b.addr {
+            bail!("segments #{i} and #{j} overlap at {:#x}", b.addr);
+        }
+    }
+
+    let lowest = segments[0].1.addr;
+    let base = args.base.unwrap_or(lowest);
+    if base > lowest {
+        bail!(
+            "the base address {base:#x} is above segment #{}, at {lowest:#x}",
+            segments[0].0,
+        );
+    }
+
+    print_header("EXPORT");
+    let colw = match container {
+        Container::Big => 19,
+        Container::Little => 11,
+    };
+    let sizew = colw + 11;
+    println!(
+        "\x1b[97m{:>7} │ {:>colw$} │ {:>sizew$} │ {:>10}\x1b[0m",
+        "Segment",
+        if args.r#virtual { "Virt. addr." } else { "Phys. addr." },
+        "Size", "Offset",
+    );
+    println!(
+        "\x1b[97m{0:─<8}┼{0:─<w$}┼{0:─<sw$}┼{0:─<12}\x1b[0m",
+        "", w = colw + 2, sw = sizew + 2,
+    );
+    for (i, chunk) in &segments {
+        let size = chunk.data.len() as u64;
+        println!(
+            "{:>7} \x1b[97m│\x1b[0m {} \x1b[97m│\x1b[0m {} {:>10} \x1b[97m│\x1b[0m {:>#10x}",
+            format!("#{i}"), sp.hex(chunk.addr), sp.hex(size), BinSize(size), chunk.addr - base,
+        );
+    }
+    println!();
+
+    // A flat binary has no addresses, gaps between segments must be filled.
+    let chunks: Vec<Chunk> = segments.into_iter().map(|(_, chunk)| chunk).collect();
+    let chunks = match (args.format, args.gap_fill) {
+        (ExportFormat::Binary, fill) => vec![flatten(&chunks, base, fill.unwrap_or(0))?],
+        (_, Some(fill)) => vec![flatten(&chunks, chunks[0].addr, fill)?],
+        (_, None) => chunks,
+    };
+
+    let out = match args.format {
+        ExportFormat::Binary => chunks[0].data.to_vec(),
+        ExportFormat::Ihex => ihex(&chunks, elf.entry)?.into_bytes(),
+        ExportFormat::Srec => {
+            let name = args.output.file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            srec(&chunks, elf.entry, &name)?.into_bytes()
+        },
+    };
+    std::fs::write(&args.output, &out).with_context(||
+        format!("couldn't write the image to '{}'", args.output.display())
+    )?;
+
+    let format = match args.format {
+        ExportFormat::Binary => format!("raw binary based at {}", sp.hex(base)),
+        ExportFormat::Ihex => "Intel HEX".to_string(),
+        ExportFormat::Srec => "Motorola S-record".to_string(),
+    };
+    println!(
+        "Wrote {} ({format}) to \"{}\"",
+        BinSize(out.len() as u64), args.output.display(),
+    );
+
+    Ok(())
+}
+
+/// Merge sorted chunks into one starting at `base`, filling gaps with `fill`.
+fn flatten<'a>(chunks: &[Chunk], base: u64, fill: u8) -> Result<Chunk<'a>> {
+    let last = chunks.last().unwrap();
+    let end = last.addr + last.data.len() as u64;
+    if end - base > MAX_IMAGE_SIZE {
+        bail!(
+            "the flat image would span {} from {base:#x} to {end:#x}, more than {}",
+            BinSize(end - base), BinSize(MAX_IMAGE_SIZE),
+        );
+    }
+    let mut image = vec![fill; (end - base) as usize];
+
+    for chunk in chunks {
+        let start = (chunk.addr - base) as usize;
+        image[start..(start + chunk.data.len())].copy_from_slice(&chunk.data);
+    }
+
+    Ok(Chunk { addr: base, data: image.into() })
+}
+
+fn ihex(chunks: &[Chunk], entry: u64) -> Result<String> {
+    fn record(out: &mut String, typ: u8, addr: u16, data: &[u8]) {
+        let mut sum = (data.len() as u8)
+            .wrapping_add((addr >> 8) as u8)
+            .wrapping_add(addr as u8)
+            .wrapping_add(typ);
+        let _ = write!(out, ":{:02X}{addr:04X}{typ:02X}", data.len());
+        for &b in data {
+            let _ = write!(out, "{b:02X}");
+            sum = sum.wrapping_add(b);
+        }
+        let _ = writeln!(out, "{:02X}", sum.wrapping_neg());
+    }
+
+    let mut out = String::new();
+    let mut upper = 0;
+    for chunk in chunks {
+        if chunk.addr + chunk.data.len() as u64 > 1 << 32 {
+            bail!("Intel HEX can't address data above 4 GiB, at {:#x}", chunk.addr);
+        }
+        for (i, data) in chunk.data.chunks(RECORD_LEN).enumerate() {
+            let mut addr = chunk.addr + (i * RECORD_LEN) as u64;
+            let mut data = data;
+            // Records can't cross a 64 KiB boundary.
+            while !data.is_empty() {
+                if addr >> 16 != upper {
+                    upper = addr >> 16;
+                    record(&mut out, 0x04, 0, &(upper as u16).to_be_bytes());
+                }
+                let room = (0x10000 - (addr & 0xffff)) as usize;
+                let (now, rest) = data.split_at(data.len().min(room));
+                record(&mut out, 0x00, addr as u16, now);
+                addr += now.len() as u64;
+                data = rest;
+            }
+        }
+    }
+    if let Ok(entry) = u32::try_from(entry) {
+        if entry != 0 {
+            record(&mut out, 0x05, 0, &entry.to_be_bytes());
+        }
+    }
+    record(&mut out, 0x01, 0, &[]);
+
+    Ok(out)
+}
+
+fn srec(chunks: &[Chunk], entry: u64, name: &str) -> Result<String> {
+    fn record(out: &mut String, typ: u8, addr: u64, addr_len: usize, data: &[u8]) {
+        let count = (addr_len + data.len() + 1) as u8;
+        let addr = &addr.to_be_bytes()[(8 - addr_len)..];
+        let mut sum = count;
+        let _ = write!(out, "S{typ}{count:02X}");
+        for &b in addr.iter().chain(data) {
+            let _ = write!(out, "{b:02X}");
+            sum = sum.wrapping_add(b);
+        }
+        let _ = writeln!(out, "{:02X}", !sum);
+    }
+
+    let end = chunks.iter()
+        .map(|c| c.addr + c.data.len() as u64)
+        .max()
+        .unwrap_or(0)
+        .max(entry.saturating_add(1));
+    let (data_type, end_type, addr_len) = match end - 1 {
+        0..=0xffff => (1, 9, 2),
+        0x10000..=0xff_ffff => (2, 8, 3),
+        0x100_0000..=0xffff_ffff => (3, 7, 4),
+        _ => bail!("S-records can't address data above 4 GiB"),
+    };
+
+    let mut out = String::new();
+    record(&mut out, 0, 0, 2, name.as_bytes());
+    let mut count = 0;
+    for chunk in chunks {
+        for (i, data) in chunk.data.chunks(RECORD_LEN).enumerate() {
+            record(&mut out, data_type, chunk.addr + (i * RECORD_LEN) as u64, addr_len, data);
+            count += 1;
+        }
+    }
+    if count <= 0xffff {
+        record(&mut out, 5, count, 2, &[]);
+    } else if count <= 0xff_ffff {
+        record(&mut out, 6, count, 3, &[]);
+    }
+    record(&mut out, end_type, entry, addr_len, &[]);
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(addr: u64, data: &[u8]) -> Chunk<'_> {
+        Chunk { addr, data: data.into() }
+    }
+
+    #[test]
+    fn ihex_checksums() {
+        let out = ihex(&[chunk(0x30, &[0x02, 0x33, 0x7a])], 0).unwrap();
+        assert_eq!(out, ":0300300002337A1E\n:00000001FF\n");
+    }
+
+    #[test]
+    fn ihex_extended_address_and_entry() {
+        let out = ihex(&[chunk(0x0800_0000, &[0x00])], 0x0800_0000).unwrap();
+        assert_eq!(out, ":020000040800F2\n:0100000000FF\n:0400000508000000EF\n:00000001FF\n");
+    }
+
+    #[test]
+    fn srec_checksums() {
+        let mut data = [0; 16];
+        data[..3].copy_from_slice(&[0x0a, 0x0a, 0x0d]);
+        let out = srec(&[chunk(0x7af0, &data)], 0, "").unwrap();
+        assert_eq!(
+            out,
+            "S0030000FC\nS1137AF00A0A0D0000000000000000000000000061\nS5030001FB\nS9030000FC\n",
+        );
+    }
+
+    #[test]
+    fn srec_address_length() {
+        let out = srec(&[chunk(0x1_0000, &[0xff])], 0x1_0000, "").unwrap();
+        assert_eq!(out, "S0030000FC\nS205010000FFFA\nS5030001FB\nS804010000FA\n");
+    }
+}
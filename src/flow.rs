@@ -0,0 +1,301 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Control flow of decoded instructions: branch targets, basic blocks, jump
+//! arrows drawn next to a listing, and Graphviz export.
+
+use std::collections::{BTreeSet, HashMap};
+use iced_x86::{FlowControl, Formatter, Instruction, OpKind};
+
+use crate::demangle::demangle;
+
+/// The maximum number of nested arrows drawn in the gutter; the shortest jumps
+/// are kept.
+const MAX_LANES: usize = 8;
+
+const LANE_COLORS: [u8; 6] = [36, 33, 35, 32, 34, 31];
+
+/// The target of a direct jump, conditional or not.
+pub fn branch_target(instr: &Instruction) -> Option<u64> {
+    match instr.flow_control() {
+        FlowControl::ConditionalBranch | FlowControl::UnconditionalBranch => (),
+        _ => return None,
+    }
+
+    match instr.op0_kind() {
+        OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64 =>
+            Some(instr.near_branch_target()),
+        _ => None,
+    }
+}
+
+/// Whether execution may not continue to the next instruction.
+fn ends_block(instr: &Instruction) -> bool {
+    !matches!(
+        instr.flow_control(),
+        FlowControl::Next | FlowControl::Call | FlowControl::IndirectCall
+            | FlowControl::Interrupt,
+    )
+}
+
+/// Addresses of the first instruction of every basic block.
+pub fn block_starts(instrs: &[Instruction]) -> BTreeSet<u64> {
+    let ips: BTreeSet<u64> = instrs.iter().map(|i| i.ip()).collect();
+    let mut starts = BTreeSet::new();
+
+    if let Some(first) = instrs.first() {
+        starts.insert(first.ip());
+    }
+    for instr in instrs {
+        if let Some(target) = branch_target(instr).filter(|t| ips.contains(t)) {
+            starts.insert(target);
+        }
+        if ends_block(instr) && ips.contains(&instr.next_ip()) {
+            starts.insert(instr.next_ip());
+        }
+    }
+
+    starts
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Edge {
+    Taken,
+    FallThrough,
+    Jump,
+}
+
+struct BasicBlock<'a> {
+    instrs: &'a [Instruction],
+    succs: Vec<(u64, Edge)>,
+    indirect: bool,
+}
+
+fn basic_blocks(instrs: &[Instruction]) -> Vec<BasicBlock<'_>> {
+    let starts = block_starts(instrs);
+    let mut blocks = Vec::new();
+    let mut first = 0;
+
+    for (i, instr) in instrs.iter().enumerate() {
+        let next = instrs.get(i + 1);
+        if next.is_some_and(|n| !starts.contains(&n.ip())) {
+            continue;
+        }
+
+        let mut succs = Vec::new();
+        let target = branch_target(instr);
+        match instr.flow_control() {
+            FlowControl::ConditionalBranch => {
+                succs.extend(target.map(|t| (t, Edge::Taken)));
+                succs.push((instr.next_ip(), Edge::FallThrough));
+            },
+            FlowControl::UnconditionalBranch => {
+                succs.extend(target.map(|t| (t, Edge::Jump)));
+            },
+            _ if !ends_block(instr) && next.is_some() => {
+                succs.push((instr.next_ip(), Edge::FallThrough));
+            },
+            _ => (),
+        }
+
+        blocks.push(BasicBlock {
+            instrs: &instrs[first..=i],
+            succs,
+            indirect: instr.flow_control() == FlowControl::IndirectBranch,
+        });
+        first = i + 1;
+    }
+
+    blocks
+}
+
+/// Print the control-flow graph of a function in Graphviz DOT. Jumps leaving
+/// the function (e.g. tail calls) point to a node named after their target.
+pub fn print_cfg(
+    name: &str,
+    instrs: &[Instruction],
+    formatter: &mut dyn Formatter,
+    syms: &HashMap<u64, String>,
+) {
+    let starts = block_starts(instrs);
+
+    println!("digraph \"{}\" {{", dot_escape(name));
+    println!("    node [shape=box, fontname=\"monospace\"];");
+    println!("    edge [fontname=\"monospace\"];");
+
+    let mut external = BTreeSet::new();
+    for block in basic_blocks(instrs) {
+        let start = block.instrs[0].ip();
+        let mut label = format!("{start:#x}:\\l");
+        for instr in block.instrs {
+            let mut text = String::new();
+            formatter.format(instr, &mut text);
+            label.push_str(&format!("  {}\\l", dot_escape(&text)));
+        }
+        if block.indirect {
+            label.push_str("  (indirect jump)\\l");
+        }
+        println!("    b_{start:x} [label=\"{label}\"];");
+
+        for &(target, edge) in &block.succs {
+            let dest = if starts.contains(&target) {
+                format!("b_{target:x}")
+            } else {
+                external.insert(target);
+                format!("ext_{target:x}")
+            };
+            let attrs = match edge {
+                Edge::Taken => "color=green",
+                Edge::FallThrough => "color=red",
+                Edge::Jump => "color=blue",
+            };
+            println!("    b_{start:x} -> {dest} [{attrs}];");
+        }
+    }
+
+    for target in external {
+        let label = match syms.get(&target) {
+            Some(name) => demangle(name).to_string(),
+            None => format!("{target:#x}"),
+        };
+        println!(
+            "    ext_{target:x} [shape=ellipse, label=\"{}\"];",
+            dot_escape(&label),
+        );
+    }
+
+    println!("}}");
+}
+
+pub fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// ASCII-art arrows linking jumps to their targets, drawn in a gutter on the
+/// left of a listing, one row per instruction.
+pub struct JumpArrows {
+    /// Vertical spans `(top, bottom, column, color)`, in rows.
+    spans: Vec<(usize, usize, usize, u8)>,
+    cells: Vec<Vec<Option<(char, u8)>>>,
+}
+
+impl JumpArrows {
+    pub fn new(instrs: &[Instruction]) -> Self {
+        let rows: HashMap<u64, usize> = instrs.iter()
+            .enumerate()
+            .map(|(i, instr)| (instr.ip(), i))
+            .collect();
+
+        let mut jumps: Vec<(usize, usize)> = instrs.iter()
+            .enumerate()
+            .filter_map(|(from, instr)| {
+                let to = *rows.get(&branch_target(instr)?)?;
+                Some((from, to))
+            })
+            .collect();
+        jumps.sort_by_key(|&(from, to)| from.abs_diff(to));
+
+        let mut lanes: Vec<Vec<(usize, usize)>> = Vec::new();
+        let mut placed = Vec::new();
+        for (from, to) in jumps {
+            let (top, bottom) = (from.min(to), from.max(to));
+            let lane = lanes.iter()
+                .position(|l| l.iter().all(|&(t, b)| bottom < t || top > b))
+                .unwrap_or(lanes.len());
+            if lane >= MAX_LANES {
+                continue;
+            }
+            if lane == lanes.len() {
+                lanes.push(Vec::new());
+            }
+            lanes[lane].push((top, bottom));
+            placed.push((from, to, lane));
+        }
+
+        let width = lanes.len() + 1;
+        let mut cells = vec![vec![None; width]; instrs.len()];
+        let mut spans = Vec::new();
+
+        for &(from, to, lane) in &placed {
+            let col = lanes.len() - 1 - lane;
+            let color = LANE_COLORS[lane % LANE_COLORS.len()];
+            let (top, bottom) = (from.min(to), from.max(to));
+            spans.push((top, bottom, col, color));
+
+            if top == bottom {
+                cells[from][width - 1] = Some(('↻', color));
+                continue;
+            }
+            for row in &mut cells[(top + 1)..bottom] {
+                row[col] = Some(('│', color));
+            }
+            cells[top][col] = Some(('╭', color));
+            cells[bottom][col] = Some(('╰', color));
+        }
+
+        for &(from, to, lane) in &placed {
+            if from == to {
+                continue;
+            }
+            let col = lanes.len() - 1 - lane;
+            let color = LANE_COLORS[lane % LANE_COLORS.len()];
+            for row in [from, to] {
+                for cell in &mut cells[row][(col + 1)..(width - 1)] {
+                    *cell = match *cell {
+                        None | Some(('─', _)) => Some(('─', color)),
+                        Some(('│', _)) => Some(('┼', color)),
+                        corner => corner,
+                    };
+                }
+            }
+            cells[to][width - 1] = Some(('►', color));
+            if cells[from][width - 1].is_none() {
+                cells[from][width - 1] = Some(('─', color));
+            }
+        }
+
+        Self { spans, cells }
+    }
+
+    /// The gutter for the instruction at `row`.
+    pub fn row(&self, row: usize) -> String {
+        let mut s = String::new();
+        for cell in &self.cells[row] {
+            match cell {
+                Some((c, color)) => s.push_str(&format!("\x1b[{color}m{c}\x1b[0m")),
+                None => s.push(' '),
+            }
+        }
+        s.push(' ');
+        s
+    }
+
+    /// The gutter for an extra line printed just before the instruction at
+    /// `row`, where only the arrows passing through are drawn.
+    pub fn between(&self, row: usize) -> String {
+        let width = self.cells.first().map_or(0, Vec::len);
+        let mut cells = vec![None; width];
+        for &(top, bottom, col, color) in &self.spans {
+            if top < row && row <= bottom {
+                cells[col] = Some(color);
+            }
+        }
+
+        let mut s = String::new();
+        for cell in cells {
+            match cell {
+                Some(color) => s.push_str(&format!("\x1b[{color}m│\x1b[0m")),
+                None => s.push(' '),
+            }
+        }
+        s.push(' ');
+        s
+    }
+}
@@ -9,66 +9,258 @@
  ******************************************************************************/
 
 use std::cell::RefCell;
-use std::collections::HashMap;
-use goblin::elf::Elf;
-use anyhow::{anyhow, Context, Result};
-use gimli::{BaseAddresses, CallFrameInstruction, EhFrame, EndianSlice,
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+use goblin::elf::{Elf, SectionHeader};
+use anyhow::{anyhow, bail, Context, Result};
+use gimli::{BaseAddresses, CallFrameInstruction, DebugFrame, EhFrame, EndianSlice,
             FrameDescriptionEntry, LittleEndian, Register, SectionBaseAddresses,
             UnwindSection};
 use goblin::container::Container;
-use goblin::elf::sym::STT_FUNC;
+use goblin::elf::sym::{STT_FILE, STT_FUNC, STT_NOTYPE};
+use goblin::elf::section_header::SHT_NOBITS;
 use iced_x86::{Decoder, DecoderOptions, Formatter, FormatterOutput,
                FormatterTextKind, GasFormatter, Instruction, IntelFormatter,
-               SymbolResolver, SymbolResult};
-use rustc_demangle::demangle;
-
-use crate::args::{FnArgs, Syntax};
-use crate::eh::EhInstrContext;
-use crate::elf::{find_symbol, find_symbol_by_addr, symbol_file_offset};
+               Mnemonic, OpKind, Register as IcedRegister, SymbolResolver,
+               SymbolResult};
+
+use crate::args::{DisasmArgs, FnArgs, Syntax};
+use crate::demangle::demangle;
+use crate::eh::{EhInstrContext, fde_ranges};
+use crate::flow::{block_starts, JumpArrows, print_cfg};
+use crate::elf::{content_at_vaddr, find_symbol_by_addr, match_symbols, symbol_file_offset,
+                 vaddr_file_offset};
 use crate::print::SizePrint;
 use crate::sections::find_section;
-use crate::sym::sym_type;
+use crate::sym::{addr_to_sym, print_candidates, sym_type};
 
 pub fn do_fn(elf: &Elf, bytes: &[u8], args: &FnArgs) -> Result<()> {
-    let (sym, strtab) = if args.address {
+    let fdes = fde_ranges(elf, bytes);
+
+    let targets = if args.address {
         let addr = u64::from_str_radix(args.name.trim_start_matches("0x"), 16)
             .context(anyhow!("couldn't parse memory address '{}'", args.name))?;
-        find_symbol_by_addr(&elf.syms, addr).zip(Some(&elf.strtab))
+
+        if let Some(sym) = find_symbol_by_addr(&elf.syms, addr) {
+            let name = elf.strtab.get_at(sym.st_name).unwrap_or("???");
+            check_sym_type(name, sym.st_type());
+            vec![(name.to_string(), sym.st_value, sym.st_size, symbol_file_offset(elf, name))]
+        } else {
+            let &(start, len) = fdes.iter()
+                .find(|&&(start, len)| (start..(start + len)).contains(&addr))
+                .ok_or_else(|| anyhow!(
+                    "couldn't find any symbol or FDE containing address {addr:#x}"
+                ))?;
+            vec![(fde_fn_name(start), start, len, vaddr_file_offset(elf, start))]
+        }
     } else {
-        find_symbol(&elf.syms, &elf.strtab, &args.name).zip(Some(&elf.strtab))
-            .or_else(|| find_symbol(&elf.dynsyms, &elf.dynstrtab, &args.name).zip(Some(&elf.dynstrtab)))
-    }.ok_or_else(||
-        anyhow!("couldn't find any symbol matching {:?}", args.name)
-    )?;
+        let matches = match_symbols(elf, &args.name, args.regex, true)?;
+        if matches.is_empty() {
+            bail!("couldn't find any symbol matching {:?}", args.name);
+        } else if matches.len() > 1 && !args.all {
+            print_candidates(elf, &args.name, &matches);
+            bail!(
+                "{} symbols match {:?}, pass `--all` to disassemble all of them",
+                matches.len(), args.name,
+            );
+        }
 
-    let sym_name = strtab.get_at(sym.st_name).unwrap();
+        matches.into_iter()
+            .map(|(sym, name)| {
+                check_sym_type(name, sym.st_type());
+                let file_off = vaddr_file_offset(elf, sym.st_value);
+                (name.to_string(), sym.st_value, sym.st_size, file_off)
+            })
+            .collect()
+    };
 
-    if sym.st_type() != STT_FUNC {
-        println!(
-            "\x1b[93mwarning\x1b[0m: Symbol {sym_name:?} has type {}", sym_type(sym.st_type())
-        );
-    }
+    for (i, (sym_name, start, size, file_off)) in targets.into_iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        let file_off = file_off.ok_or_else(||
+            anyhow!("couldn't find the file offset")
+        )? as usize;
+        let size = if size == 0 {
+            infer_fn_size(elf, bytes, &fdes, start).ok_or_else(||
+                anyhow!("couldn't infer the size of {sym_name:?}")
+            )?
+        } else {
+            size
+        };
+
+        let content = bytes.get(file_off..(file_off + size as usize))
+            .ok_or_else(|| anyhow!("{sym_name:?} goes past the end of the file"))?;
+
+        if !args.cfg {
+            println!("\x1b[97m{sym_name}:\x1b[0m");
+        }
 
-    let file_off = symbol_file_offset(elf, sym_name).ok_or_else(||
-        anyhow!("couldn't find the file offset")
-    )? as usize;
-    if sym.st_size == 0 {
-        unimplemented!("symbol is required");
+        let opts = DisassOptions {
+            cfi: args.cfi,
+            syntax: args.syntax,
+            labels: BTreeMap::new(),
+            jumps: args.jumps,
+            cfg: args.cfg.then(|| demangle(&sym_name).to_string()),
+        };
+        disassemble(elf, bytes, start, content, opts);
     }
 
-    let content = &bytes[file_off..(file_off + sym.st_size as usize)];
+    Ok(())
+}
+
+pub fn do_disasm(elf: &Elf, bytes: &[u8], args: &DisasmArgs) -> Result<()> {
+    let (start, content) = if let Some(start) = args.start {
+        let end = match (args.end, args.len) {
+            (Some(end), _) => end,
+            (None, Some(len)) => start.checked_add(len).ok_or_else(|| anyhow!(
+                "the range of {len:#x} bytes from {start:#x} goes past the end of the address space"
+            ))?,
+            (None, None) => {
+                let sh = section_by_vaddr(elf, start).ok_or_else(|| anyhow!(
+                    "no section contains address {start:#x}, specify `--end` or `--len`"
+                ))?;
+                sh.sh_addr.saturating_add(sh.sh_size)
+            },
+        };
+        if end <= start {
+            bail!("the end address {end:#x} is before the start address {start:#x}");
+        }
+
+        let content = content_at_vaddr(elf, bytes, start)
+            .and_then(|c| c.get(..((end - start) as usize)))
+            .ok_or_else(|| anyhow!(
+                "range {start:#x}..{end:#x} is not entirely backed by the file content"
+            ))?;
+        (start, content)
+    } else {
+        let name = args.section.as_deref().unwrap_or(".text");
+        let sh = find_section(elf, name)
+            .ok_or_else(|| anyhow!("couldn't find section {name:?}"))?;
+        if !sh.is_executable() {
+            println!("\x1b[93mwarning\x1b[0m: section {name:?} is not executable");
+        }
+        let content = sh.file_range()
+            .and_then(|r| bytes.get(r))
+            .ok_or_else(|| anyhow!("section {name:?} has no content"))?;
+        (sh.sh_addr, content)
+    };
 
-    println!("\x1b[97m{sym_name}:\x1b[0m");
+    let end = start.saturating_add(content.len() as u64);
+    let fdes = fde_ranges(elf, bytes);
+    let labels = code_labels(elf, bytes, &fdes, start..end);
+
+    if !labels.contains_key(&start) {
+        if let Some(sym) = addr_to_sym(&elf.syms, start) {
+            let name = elf.strtab.get_at(sym.st_name).unwrap_or("???");
+            println!(
+                "\x1b[97m{} + {:#x}:\x1b[0m",
+                demangle(name), start - sym.st_value,
+            );
+        }
+    }
 
     let opts = DisassOptions {
         cfi: args.cfi,
         syntax: args.syntax,
+        labels,
+        jumps: args.jumps,
+        cfg: None,
     };
-    disassemble(elf, bytes, sym.st_value, content, opts);
+    disassemble(elf, bytes, start, content, opts);
 
     Ok(())
 }
 
+fn check_sym_type(sym_name: &str, st_type: u8) {
+    if st_type != STT_FUNC {
+        println!(
+            "\x1b[93mwarning\x1b[0m: Symbol {sym_name:?} has type {}", sym_type(st_type)
+        );
+    }
+}
+
+/// The name given to functions that have an FDE but no symbol.
+fn fde_fn_name(addr: u64) -> String {
+    format!("fn_{addr:x}")
+}
+
+/// Guess the size of a function whose symbol has no size: use the FDE starting
+/// at this address, or else stop at the next label or at the section's end.
+pub fn infer_fn_size(
+    elf: &Elf,
+    bytes: &[u8],
+    fdes: &[(u64, u64)],
+    start: u64,
+) -> Option<u64> {
+    if let Some(&(_, len)) = fdes.iter().find(|&&(s, _)| s == start) {
+        return Some(len);
+    }
+
+    let sh = section_by_vaddr(elf, start)?;
+    let end = sh.sh_addr.saturating_add(sh.sh_size);
+    let next = code_labels(elf, bytes, fdes, (start + 1)..end)
+        .into_keys()
+        .next()
+        .unwrap_or(end);
+
+    Some(next - start)
+}
+
+/// The allocated section containing `vaddr`.
+fn section_by_vaddr<'a>(elf: &'a Elf, vaddr: u64) -> Option<&'a SectionHeader> {
+    elf.section_headers.iter()
+        .filter(|sh| sh.is_alloc())
+        .find(|sh| vaddr.checked_sub(sh.sh_addr).is_some_and(|off| off < sh.sh_size))
+}
+
+/// Collect the names of all code locations within `range`: function symbols,
+/// and functions inferred from FDEs when they have no symbol.
+pub fn code_labels(
+    elf: &Elf,
+    bytes: &[u8],
+    fdes: &[(u64, u64)],
+    range: Range<u64>,
+) -> BTreeMap<u64, String> {
+    let mut labels = BTreeMap::new();
+
+    let tabs = [(&elf.syms, &elf.strtab), (&elf.dynsyms, &elf.dynstrtab)];
+    for (syms, strtab) in tabs {
+        for sym in syms.iter() {
+            if sym.st_shndx == 0 || !range.contains(&sym.st_value) {
+                continue;
+            }
+            let Some(name) = strtab.get_at(sym.st_name) else { continue };
+            let is_code = match sym.st_type() {
+                STT_FUNC => true,
+                STT_NOTYPE => !name.is_empty()
+                    && !name.starts_with('$')
+                    && !name.starts_with(".L"),
+                _ => false,
+            };
+            if is_code {
+                labels.entry(sym.st_value)
+                    .or_insert_with(|| demangle(name).to_string());
+            }
+        }
+    }
+
+    for (addr, name) in plt_symbols(elf, bytes) {
+        if range.contains(&addr) {
+            labels.entry(addr).or_insert(name);
+        }
+    }
+
+    for &(start, _) in fdes {
+        if range.contains(&start) {
+            labels.entry(start).or_insert_with(|| fde_fn_name(start));
+        }
+    }
+
+    labels
+}
+
 struct SymResolver {
     syms: HashMap<u64, String>,
     demangle: bool,
@@ -86,7 +278,7 @@ impl SymbolResolver for SymResolver {
     ) -> Option<SymbolResult<'_>> {
         if let Some(name) = self.syms.get(&address) {
             if self.demangle {
-                self.sym_name = demangle(&name).to_string();
+                self.sym_name = demangle(name).to_string();
             } else {
                 self.sym_name = name.clone();
             };
@@ -97,7 +289,7 @@ impl SymbolResolver for SymResolver {
     }
 }
 
-struct ColorOutput;
+pub struct ColorOutput;
 
 impl FormatterOutput for ColorOutput {
     fn write(&mut self, text: &str, kind: FormatterTextKind) {
@@ -120,6 +312,14 @@ impl FormatterOutput for ColorOutput {
 struct DisassOptions {
     syntax: Syntax,
     cfi: bool,
+    /// Names to print before the instruction at these addresses; decoding
+    /// restarts at each label.
+    labels: BTreeMap<u64, String>,
+    /// Draw jump arrows and basic block boundaries.
+    jumps: bool,
+    /// Print the control-flow graph in Graphviz DOT under this name, instead
+    /// of a listing.
+    cfg: Option<String>,
 }
 
 fn disassemble(elf: &Elf, bytes: &[u8], ip: u64, content: &[u8], opts: DisassOptions) {
@@ -130,27 +330,85 @@ fn disassemble(elf: &Elf, bytes: &[u8], ip: u64, content: &[u8], opts: DisassOpt
     };
     let sp = SizePrint::new(container);
 
-    let mut decoder = Decoder::with_ip(
-        bitness,
-        content,
-        ip,
-        DecoderOptions::NONE
-    );
+    let syms = resolver_symbols(elf, bytes);
+    let annotator = Annotator {
+        elf,
+        bytes,
+        got: got_symbols(elf),
+    };
+
+    let cfg_syms = opts.cfg.is_some().then(|| syms.clone());
+    let mut formatter = new_formatter(syms, opts.syntax);
+
+    if let (Some(name), Some(syms)) = (&opts.cfg, cfg_syms) {
+        let instrs: Vec<Instruction> = Decoder::with_ip(
+            bitness,
+            content,
+            ip,
+            DecoderOptions::NONE,
+        ).into_iter().collect();
+        print_cfg(name, &instrs, formatter.as_mut(), &syms);
+        return;
+    }
+
+    let end = ip + content.len() as u64;
+    let mut chunks = vec![ip];
+    chunks.extend(opts.labels.range((ip + 1)..end).map(|(&addr, _)| addr));
+    chunks.push(end);
 
-    let syms: HashMap<u64, String> = elf.syms.iter()
+    for (i, chunk) in chunks.windows(2).enumerate() {
+        let (chunk_start, chunk_end) = (chunk[0], chunk[1]);
+        if let Some(label) = opts.labels.get(&chunk_start) {
+            if i > 0 {
+                println!();
+            }
+            println!("\x1b[97m{label}:\x1b[0m");
+        }
+
+        let chunk_content = &content[((chunk_start - ip) as usize)..((chunk_end - ip) as usize)];
+        let instrs: Vec<Instruction> = Decoder::with_ip(
+            bitness,
+            chunk_content,
+            chunk_start,
+            DecoderOptions::NONE
+        ).into_iter().collect();
+        let eh = opts.cfi.then(|| EhFnCtx::new(elf, bytes, chunk_start)).flatten();
+
+        disassemble_chunk(
+            &instrs, chunk_content, formatter.as_mut(), eh,
+            &annotator, sp, opts.jumps,
+        );
+    }
+}
+
+/// Names of all addresses that instruction operands can be resolved to:
+/// defined symbols, PLT stubs, and functions inferred from FDEs.
+pub fn resolver_symbols(elf: &Elf, bytes: &[u8]) -> HashMap<u64, String> {
+    let mut syms: HashMap<u64, String> = elf.syms.iter()
+        .filter(|sym| sym.st_shndx != 0 && sym.st_type() != STT_FILE)
         .filter_map(|sym|
             elf.strtab.get_at(sym.st_name)
+                .filter(|name| !name.is_empty())
                 .map(|name| (sym.st_value, name.to_owned()))
         ).collect();
+    for (addr, name) in plt_symbols(elf, bytes) {
+        syms.entry(addr).or_insert(name);
+    }
+    for (start, _) in fde_ranges(elf, bytes) {
+        syms.entry(start).or_insert_with(|| fde_fn_name(start));
+    }
 
+    syms
+}
+
+pub fn new_formatter(syms: HashMap<u64, String>, syntax: Syntax) -> Box<dyn Formatter> {
     let sym_resolver: Box<dyn SymbolResolver> = Box::new(SymResolver {
         syms,
         demangle: true,
         sym_name: String::new(),
     });
 
-    let mut output = ColorOutput;
-    let mut formatter: Box<dyn Formatter> = match opts.syntax {
+    let mut formatter: Box<dyn Formatter> = match syntax {
         Syntax::Intel => Box::new(IntelFormatter::with_options(Some(sym_resolver), None)),
         Syntax::Att => Box::new(GasFormatter::with_options(Some(sym_resolver), None)),
     };
@@ -160,46 +418,210 @@ fn disassemble(elf: &Elf, bytes: &[u8], ip: u64, content: &[u8], opts: DisassOpt
     formatter.options_mut().set_space_between_memory_add_operators(true);
     formatter.options_mut().set_gas_space_after_memory_operand_comma(true);
 
-    let mut eh = opts.cfi.then(|| EhFnCtx::new(elf, bytes, ip)).flatten();
+    formatter
+}
+
+fn disassemble_chunk(
+    instrs: &[Instruction],
+    content: &[u8],
+    formatter: &mut dyn Formatter,
+    mut eh: Option<EhFnCtx>,
+    annotator: &Annotator,
+    sp: SizePrint,
+    jumps: bool,
+) {
+    let mut output = ColorOutput;
+    let ip = instrs.first().map_or(0, Instruction::ip);
+    let (arrows, block_starts) = if jumps {
+        (Some(JumpArrows::new(instrs)), block_starts(instrs))
+    } else {
+        (None, BTreeSet::new())
+    };
 
-    while decoder.can_decode() {
-        let instr = decoder.decode();
+    for (row, instr) in instrs.iter().enumerate() {
         let start_index = (instr.ip() - ip) as usize;
         let bytes = &content[start_index..(start_index + instr.len())];
+        let between = arrows.as_ref().map(|a| a.between(row)).unwrap_or_default();
 
+        if row > 0 && block_starts.contains(&instr.ip()) {
+            println!("{between}\x1b[90m{}\x1b[0m", "┈".repeat(sp.hex(0).to_string().len() + 30));
+        }
         if let Some(ref mut eh) = eh {
-            eh.at_ip(instr.ip());
+            eh.at_ip(instr.ip(), &between);
         }
 
+        if let Some(ref arrows) = arrows {
+            print!("{}", arrows.row(row));
+        }
         print!("{} \x1b[97m│\x1b[0m  ", sp.hex(instr.ip()));
 
-        let col_w;
+        print_instr_bytes(bytes);
+        formatter.format(instr, &mut output);
+        if let Some(comment) = annotator.comment(instr) {
+            print!("  \x1b[90m# {comment}");
+        }
+        println!("\x1b[0m");
+    }
+}
+
+/// Provides comments for instructions referencing memory: the symbol a GOT
+/// slot is relocated to, or the content of a string literal.
+struct Annotator<'a> {
+    elf: &'a Elf<'a>,
+    bytes: &'a [u8],
+    got: HashMap<u64, String>,
+}
+
+impl Annotator<'_> {
+    fn comment(&self, instr: &Instruction) -> Option<String> {
+        for i in 0..instr.op_count() {
+            let addr = match instr.op_kind(i) {
+                OpKind::Memory => match instr.memory_base() {
+                    IcedRegister::RIP | IcedRegister::EIP | IcedRegister::None
+                        if instr.memory_index() == IcedRegister::None =>
+                        instr.memory_displacement64(),
+                    _ => continue,
+                },
+                OpKind::Immediate32 | OpKind::Immediate64
+                | OpKind::Immediate32to64 => instr.immediate(i),
+                _ => continue,
+            };
 
-        if bytes.len() > 12 {
-            for &byte in bytes {
-                print!("{byte:02x}");
+            if instr.op_kind(i) == OpKind::Memory {
+                if let Some(name) = self.got.get(&addr) {
+                    return Some(format!("{}@got", demangle(name)));
+                }
             }
-            col_w = bytes.len() * 2;
-        } else {
-            for &byte in bytes {
-                print!("{byte:02x} ");
+            if let Some(s) = rodata_string(self.elf, self.bytes, addr) {
+                return Some(s);
             }
-            col_w = bytes.len() * 3;
         }
 
-        print!(
-            "{:w$} \x1b[97m│\x1b[0m  ", "",
-            w = 24usize.saturating_sub(col_w)
+        None
+    }
+}
+
+/// Print the column of an instruction's bytes, followed by a separator.
+pub fn print_instr_bytes(bytes: &[u8]) {
+    let col_w = if bytes.len() > 12 {
+        for &byte in bytes {
+            print!("{byte:02x}");
+        }
+        bytes.len() * 2
+    } else {
+        for &byte in bytes {
+            print!("{byte:02x} ");
+        }
+        bytes.len() * 3
+    };
+
+    print!(
+        "{:w$} \x1b[97m│\x1b[0m  ", "",
+        w = 24usize.saturating_sub(col_w)
+    );
+}
+
+/// Map GOT slots to the name of the symbol their dynamic relocation refers to.
+pub fn got_symbols(elf: &Elf) -> HashMap<u64, String> {
+    elf.dynrelas.iter()
+        .chain(elf.dynrels.iter())
+        .chain(elf.pltrelocs.iter())
+        .filter(|rel| rel.r_sym != 0)
+        .filter_map(|rel| {
+            let sym = elf.dynsyms.get(rel.r_sym)?;
+            let name = elf.dynstrtab.get_at(sym.st_name)?;
+            Some((rel.r_offset, name.to_owned()))
+        })
+        .collect()
+}
+
+/// Map PLT stubs in `.plt`, `.plt.sec`, and `.plt.got` to `name@plt`, by
+/// following the GOT slot through which each stub jumps.
+pub fn plt_symbols(elf: &Elf, bytes: &[u8]) -> HashMap<u64, String> {
+    let bitness = if elf.is_64 { 64 } else { 32 };
+    let got = got_symbols(elf);
+    let got_plt = find_section(elf, ".got.plt")
+        .or_else(|| find_section(elf, ".got"))
+        .map(|sh| sh.sh_addr);
+    let mut syms = HashMap::new();
+
+    for name in [".plt", ".plt.sec", ".plt.got"] {
+        let Some(sh) = find_section(elf, name) else { continue };
+        let Some(content) = sh.file_range().and_then(|r| bytes.get(r)) else {
+            continue
+        };
+        let entsize = if sh.sh_entsize > 0 { sh.sh_entsize } else { 16 };
+
+        let mut decoder = Decoder::with_ip(
+            bitness, content, sh.sh_addr, DecoderOptions::NONE,
         );
+        while decoder.can_decode() {
+            let instr = decoder.decode();
+            if instr.mnemonic() != Mnemonic::Jmp
+                || instr.op0_kind() != OpKind::Memory {
+                continue;
+            }
 
-        formatter.format(&instr, &mut output);
-        println!("\x1b[0m");
+            let slot = match instr.memory_base() {
+                IcedRegister::RIP | IcedRegister::EIP | IcedRegister::None =>
+                    instr.memory_displacement64(),
+                IcedRegister::EBX => match got_plt {
+                    Some(base) => base.wrapping_add(instr.memory_displacement32() as i32 as u64),
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            if let Some(target) = got.get(&slot) {
+                let stub = sh.sh_addr + (instr.ip() - sh.sh_addr) / entsize * entsize;
+                syms.entry(stub).or_insert_with(|| format!("{target}@plt"));
+            }
+        }
+    }
+
+    syms
+}
+
+/// If `addr` points to a NUL-terminated printable string in a `.rodata*`
+/// section, return it quoted, and shortened if too long.
+pub fn rodata_string(elf: &Elf, bytes: &[u8], addr: u64) -> Option<String> {
+    const MAX_LEN: usize = 48;
+
+    let sh = elf.section_headers.iter().find(|sh| {
+        sh.sh_type != SHT_NOBITS
+            && sh.sh_addr != 0
+            && sh.vm_range().contains(&(addr as usize))
+            && elf.shdr_strtab.get_at(sh.sh_name)
+                .map(|n| n.starts_with(".rodata"))
+                .unwrap_or(false)
+    })?;
+    let start = (sh.sh_offset + (addr - sh.sh_addr)) as usize;
+    let end = (sh.sh_offset + sh.sh_size) as usize;
+    let data = bytes.get(start..end)?;
+
+    let len = data.iter().position(|&b| b == 0)?;
+    let s = std::str::from_utf8(&data[..len]).ok()?;
+    if s.is_empty()
+        || s.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        return None;
+    }
+
+    if s.chars().count() > MAX_LEN {
+        let short: String = s.chars().take(MAX_LEN).collect();
+        Some(format!("{short:?}…"))
+    } else {
+        Some(format!("{s:?}"))
     }
 }
 
+enum FrameSection<'a> {
+    Eh(EhFrame<EndianSlice<'a, LittleEndian>>),
+    Debug(DebugFrame<EndianSlice<'a, LittleEndian>>),
+}
+
 struct EhFnCtx<'a> {
     base_addrs: &'static BaseAddresses,
-    eh: EhFrame<EndianSlice<'a, LittleEndian>>,
+    section: FrameSection<'a>,
     fde: FrameDescriptionEntry<EndianSlice<'a, LittleEndian>>,
     instr_ctx: RefCell<EhInstrContext>,
     curr_loc: RefCell<u64>,
@@ -212,36 +634,57 @@ impl<'a> EhFnCtx<'a> {
         let container = elf.header.container().unwrap_or(Container::Big);
         let sp = SizePrint::new(container);
 
-        let eh_frame = find_section(elf, ".eh_frame")?;
-        let eh = EhFrame::new(&bytes[eh_frame.file_range()?], LittleEndian);
-
+        let eh_frame = find_section(elf, ".eh_frame");
         let base_addrs = Box::leak(Box::new(BaseAddresses {
             eh_frame_hdr: SectionBaseAddresses::default(),
             eh_frame: SectionBaseAddresses {
-                section: Some(eh_frame.sh_addr),
+                section: eh_frame.map(|sh| sh.sh_addr),
                 text: None,
                 data: None,
             },
         }));
 
-        let fde = eh.fde_for_address(
-            &base_addrs,
-            ip,
-            |section, bases, offset| section.cie_from_offset(bases, offset),
-        ).ok()?;
+        let eh_fde = eh_frame
+            .and_then(|sh| bytes.get(sh.file_range()?))
+            .and_then(|content| {
+                let eh = EhFrame::new(content, LittleEndian);
+                let fde = eh.fde_for_address(
+                    base_addrs,
+                    ip,
+                    |section, bases, offset| section.cie_from_offset(bases, offset),
+                ).ok()?;
+                Some((FrameSection::Eh(eh), fde))
+            });
+
+        let (section, fde) = match eh_fde {
+            Some(eh_fde) => eh_fde,
+            None => {
+                let sh = find_section(elf, ".debug_frame")?;
+                let content = bytes.get(sh.file_range()?)?;
+                let mut debug = DebugFrame::new(content, LittleEndian);
+                debug.set_address_size(if elf.is_64 { 8 } else { 4 });
+                let fde = debug.fde_for_address(
+                    base_addrs,
+                    ip,
+                    |section, bases, offset| section.cie_from_offset(bases, offset),
+                ).ok()?;
+                (FrameSection::Debug(debug), fde)
+            },
+        };
 
         let instr_ctx = EhInstrContext {
             cfa_reg: Register(0),
             cfa_off: 0,
             loc: fde.initial_address(),
             data_align: fde.cie().data_alignment_factor(),
+            machine: elf.header.e_machine,
             sp,
         };
         let curr_loc = instr_ctx.loc;
 
         Some(EhFnCtx {
             base_addrs,
-            eh,
+            section,
             fde,
             instr_ctx: RefCell::new(instr_ctx),
             curr_loc: RefCell::new(curr_loc),
@@ -250,16 +693,28 @@ impl<'a> EhFnCtx<'a> {
         })
     }
 
-    fn at_ip(&mut self, ip: u64) {
+    /// Print the CFI instructions up to `ip`, each line starting with
+    /// `prefix`.
+    fn at_ip(&mut self, ip: u64, prefix: &str) {
         if !self.cie_shown {
-            let mut iter = self.fde.cie().instructions(&self.eh, self.base_addrs);
+            let mut iter = match self.section {
+                FrameSection::Eh(ref eh) =>
+                    self.fde.cie().instructions(eh, self.base_addrs),
+                FrameSection::Debug(ref debug) =>
+                    self.fde.cie().instructions(debug, self.base_addrs),
+            };
             while let Ok(Some(instr)) = iter.next() {
-                self.print_instr(instr);
+                self.print_instr(instr, prefix);
             }
             self.cie_shown = true;
         }
 
-        let mut iter = self.fde.instructions(&self.eh, self.base_addrs);
+        let mut iter = match self.section {
+            FrameSection::Eh(ref eh) =>
+                self.fde.instructions(eh, self.base_addrs),
+            FrameSection::Debug(ref debug) =>
+                self.fde.instructions(debug, self.base_addrs),
+        };
         for _ in 0..self.instr_index {
             if iter.next().ok().flatten().is_none() {
                 return;
@@ -270,7 +725,7 @@ impl<'a> EhFnCtx<'a> {
             if ip < *self.curr_loc.borrow() {
                 break;
             }
-            self.print_instr(instr);
+            self.print_instr(instr, prefix);
             self.instr_index += 1;
         }
     }
@@ -278,6 +733,7 @@ impl<'a> EhFnCtx<'a> {
     fn print_instr(
         &self,
         instr: CallFrameInstruction<EndianSlice<'a, LittleEndian>>,
+        prefix: &str,
     ) {
         match instr {
             CallFrameInstruction::Nop => (),
@@ -285,7 +741,7 @@ impl<'a> EhFnCtx<'a> {
                 *self.curr_loc.borrow_mut() += delta as u64;
             },
             _ => {
-                print!("\x1b[35m[CFI]\x1b[0m ");
+                print!("{prefix}\x1b[35m[CFI]\x1b[0m ");
                 self.instr_ctx.borrow_mut().print(instr.clone());
             }
         }
@@ -0,0 +1,168 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Search of executable sections for byte patterns or instructions.
+
+use std::ops::Range;
+use anyhow::{anyhow, bail, Result};
+use goblin::container::Container;
+use goblin::elf::Elf;
+use iced_x86::{Decoder, DecoderOptions, Formatter, Instruction};
+use regex::Regex;
+
+use crate::args::GrepArgs;
+use crate::demangle::demangle;
+use crate::elf::content_at_vaddr;
+use crate::func::{ColorOutput, new_formatter, print_instr_bytes, resolver_symbols};
+use crate::print::SizePrint;
+use crate::sym::addr_to_sym;
+use crate::xrefs::exec_sections;
+
+pub fn grep(elf: &Elf, bytes: &[u8], args: &GrepArgs) -> Result<()> {
+    let bitness = if elf.is_64 { 64 } else { 32 };
+    let sp = SizePrint::new(elf.header.container().unwrap_or(Container::Big));
+    let syms = resolver_symbols(elf, bytes);
+    let mut formatter = new_formatter(syms.clone(), args.syntax);
+    // Instructions are matched without the padding that aligns operands.
+    let mut match_formatter = new_formatter(syms, args.syntax);
+    match_formatter.options_mut().set_first_operand_char_index(0);
+
+    let matcher = if args.insn {
+        let re = Regex::new(&args.pattern)
+            .map_err(|e| anyhow!("invalid instruction regex: {e}"))?;
+        Matcher::Insn(re)
+    } else {
+        Matcher::Bytes(parse_byte_pattern(&args.pattern)?)
+    };
+
+    let mut count = 0;
+    for (addr, content) in exec_sections(elf, bytes)? {
+        let instrs: Vec<Instruction> = Decoder::with_ip(
+            bitness,
+            content,
+            addr,
+            DecoderOptions::NONE,
+        ).into_iter().collect();
+
+        let hits: Vec<Range<u64>> = match matcher {
+            Matcher::Bytes(ref pattern) => byte_matches(content, pattern)
+                .map(|off| {
+                    let start = addr + off as u64;
+                    start..(start + pattern.len() as u64)
+                })
+                .collect(),
+            Matcher::Insn(ref re) => instrs.iter()
+                .filter(|instr| {
+                    let mut text = String::new();
+                    match_formatter.format(instr, &mut text);
+                    re.is_match(&text)
+                })
+                .map(|instr| instr.ip()..instr.next_ip())
+                .collect(),
+        };
+
+        for hit in hits {
+            print_hit(elf, bytes, &sp, &instrs, &hit, args.context, formatter.as_mut());
+            count += 1;
+        }
+    }
+
+    println!("{count} match(es)");
+
+    Ok(())
+}
+
+enum Matcher {
+    Bytes(Vec<Option<u8>>),
+    Insn(Regex),
+}
+
+/// Parse a pattern of hexadecimal bytes, where `??` matches any byte. Bytes
+/// may be separated by spaces, e.g. `e8 ?? ?? ?? ??` or `f00fb1`.
+pub fn parse_byte_pattern(s: &str) -> Result<Vec<Option<u8>>> {
+    let digits: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        bail!("the byte pattern must be made of pairs of hexadecimal digits or `??`");
+    }
+
+    digits.chunks(2)
+        .map(|pair| match pair {
+            ['?', '?'] => Ok(None),
+            [hi, lo] => {
+                let byte = format!("{hi}{lo}");
+                u8::from_str_radix(&byte, 16)
+                    .map(Some)
+                    .map_err(|_| anyhow!("invalid byte {byte:?} in pattern"))
+            },
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn byte_matches<'a>(
+    content: &'a [u8],
+    pattern: &'a [Option<u8>],
+) -> impl Iterator<Item = usize> + 'a {
+    content.windows(pattern.len())
+        .enumerate()
+        .filter(move |(_, window)| {
+            window.iter()
+                .zip(pattern)
+                .all(|(b, p)| p.is_none_or(|p| p == *b))
+        })
+        .map(|(off, _)| off)
+}
+
+fn print_hit(
+    elf: &Elf,
+    bytes: &[u8],
+    sp: &SizePrint,
+    instrs: &[Instruction],
+    hit: &Range<u64>,
+    context: usize,
+    formatter: &mut dyn Formatter,
+) {
+    let sym = addr_to_sym(&elf.syms, hit.start)
+        .filter(|sym| sym.st_shndx != 0)
+        .and_then(|sym| Some((sym.st_value, elf.strtab.get_at(sym.st_name)?)))
+        .filter(|(_, name)| !name.is_empty());
+    match sym {
+        Some((value, name)) => println!(
+            "\x1b[97m{} + {:#x}\x1b[0m:",
+            demangle(name), hit.start - value,
+        ),
+        None => println!("\x1b[97m{}\x1b[0m:", sp.hex(hit.start)),
+    }
+
+    // Instructions overlapping the hit, which may not start on an instruction
+    // boundary when matching bytes.
+    let first = instrs.partition_point(|i| i.next_ip() <= hit.start);
+    let last = instrs.partition_point(|i| i.ip() < hit.end);
+    let from = first.saturating_sub(context);
+    let to = (last + context).min(instrs.len());
+
+    for (i, instr) in instrs.iter().enumerate().take(to).skip(from) {
+        if (first..last).contains(&i) {
+            print!("\x1b[91m►\x1b[0m ");
+        } else {
+            print!("  ");
+        }
+        print!("{} \x1b[97m│\x1b[0m  ", sp.hex(instr.ip()));
+
+        let start = instr.ip();
+        let instr_bytes = content_at_vaddr(elf, bytes, start)
+            .and_then(|c| c.get(..instr.len()))
+            .unwrap_or(&[]);
+        print_instr_bytes(instr_bytes);
+        formatter.format(instr, &mut ColorOutput);
+        println!("\x1b[0m");
+    }
+    println!();
+}
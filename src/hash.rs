@@ -0,0 +1,348 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Symbol hash tables, SysV `.hash` and GNU `.gnu.hash`, used by the dynamic
+//! linker to look dynamic symbols up.
+
+use anyhow::{anyhow, bail, Result};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use goblin::container::Container;
+use goblin::elf::Elf;
+
+use crate::demangle::demangle;
+use crate::print::{PairTable, print_header, SizePrint};
+
+struct Words<'a> {
+    content: &'a [u8],
+    little_endian: bool,
+}
+
+impl Words<'_> {
+    fn u32(&self, idx: usize) -> Result<u32> {
+        let data = self.content.get((idx * 4)..(idx * 4 + 4))
+            .ok_or_else(|| anyhow!("the hash table is truncated"))?;
+        Ok(if self.little_endian {
+            LittleEndian::read_u32(data)
+        } else {
+            BigEndian::read_u32(data)
+        })
+    }
+
+    fn uint(&self, offset: usize, size: usize) -> Result<u64> {
+        let data = self.content.get(offset..(offset + size))
+            .ok_or_else(|| anyhow!("the hash table is truncated"))?;
+        Ok(if self.little_endian {
+            LittleEndian::read_uint(data, size)
+        } else {
+            BigEndian::read_uint(data, size)
+        })
+    }
+}
+
+/// The header of a `.gnu.hash` table, and accessors to its words.
+struct GnuHash<'a> {
+    words: Words<'a>,
+    word_size: usize,
+    nbuckets: usize,
+    symoffset: usize,
+    bloom_size: usize,
+    bloom_shift: u32,
+}
+
+impl<'a> GnuHash<'a> {
+    fn parse(elf: &Elf, content: &'a [u8]) -> Result<Self> {
+        let words = Words { content, little_endian: elf.little_endian };
+        Ok(Self {
+            word_size: if elf.is_64 { 8 } else { 4 },
+            nbuckets: words.u32(0)? as usize,
+            symoffset: words.u32(1)? as usize,
+            bloom_size: words.u32(2)? as usize,
+            bloom_shift: words.u32(3)?,
+            words,
+        })
+    }
+
+    fn bloom(&self, i: usize) -> Result<u64> {
+        self.words.uint(16 + i * self.word_size, self.word_size)
+    }
+
+    /// The index of the first bucket, in 32-bit words.
+    fn buckets(&self) -> usize {
+        4 + self.bloom_size * self.word_size / 4
+    }
+
+    fn bucket(&self, i: usize) -> Result<u32> {
+        self.words.u32(self.buckets() + i)
+    }
+
+    fn chain(&self, sym_idx: usize) -> Result<u32> {
+        let idx = sym_idx.checked_sub(self.symoffset)
+            .ok_or_else(|| anyhow!("symbol #{sym_idx} isn't hashed"))?;
+        self.words.u32(self.buckets() + self.nbuckets + idx)
+    }
+}
+
+/// The number of dynamic symbols and the size of a `.gnu.hash` table. Symbols
+/// below `symoffset` aren't hashed; the last hashed one is found by walking
+/// the chain of the highest bucket to its end.
+pub fn gnu_hash_layout(elf: &Elf, content: &[u8]) -> Result<(u64, u64)> {
+    let table = GnuHash::parse(elf, content)?;
+    let mut max = 0;
+    for i in 0..table.nbuckets {
+        max = max.max(table.bucket(i)? as usize);
+    }
+
+    let count = if max < table.symoffset {
+        table.symoffset
+    } else {
+        let mut idx = max;
+        while table.chain(idx)? & 1 == 0 {
+            idx += 1;
+        }
+        idx + 1
+    };
+    let nchains = count - table.symoffset.min(count);
+    let size = (table.buckets() + table.nbuckets + nchains) * 4;
+
+    Ok((count as u64, size as u64))
+}
+
+/// The number of dynamic symbols, which is the number of chains, and the size
+/// of a SysV `.hash` table.
+pub fn sysv_hash_layout(elf: &Elf, content: &[u8]) -> Result<(u64, u64)> {
+    let words = Words { content, little_endian: elf.little_endian };
+    let nbucket = words.u32(0)? as u64;
+    let nchain = words.u32(1)? as u64;
+
+    Ok((nchain, (2 + nbucket + nchain) * 4))
+}
+
+/// The hash function of SysV `.hash` tables.
+fn sysv_hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 0;
+    for &c in name {
+        h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xf000_0000;
+        if g != 0 {
+            h ^= g >> 24;
+        }
+        h &= !g;
+    }
+    h
+}
+
+/// The hash function of `.gnu.hash` tables (DJB's).
+fn gnu_hash(name: &[u8]) -> u32 {
+    name.iter().fold(5381u32, |h, &c| h.wrapping_mul(33).wrapping_add(c as u32))
+}
+
+pub fn sysv_hash_table(elf: &Elf, content: &[u8], lookup: Option<&str>) -> Result<()> {
+    let words = Words { content, little_endian: elf.little_endian };
+    let nbucket = words.u32(0)? as usize;
+    let nchain = words.u32(1)? as usize;
+    let bucket = |i: usize| words.u32(2 + i);
+    let chain = |i: usize| words.u32(2 + nbucket + i);
+
+    if let Some(name) = lookup {
+        if nbucket == 0 {
+            bail!("the hash table has no bucket");
+        }
+        let hash = sysv_hash(name.as_bytes());
+        let idx = hash as usize % nbucket;
+        print_lookup_header(name, hash, idx);
+
+        let mut sym_idx = bucket(idx)? as usize;
+        // A chain can't be longer than the number of symbols, unless it loops.
+        for _ in 0..nchain {
+            if sym_idx == 0 {
+                break;
+            }
+            if print_candidate(elf, sym_idx, name) {
+                return Ok(());
+            }
+            sym_idx = chain(sym_idx)? as usize;
+        }
+        bail!("{name:?} isn't in the hash table");
+    }
+
+    let mut lengths = Vec::with_capacity(nbucket.min(content.len() / 4));
+    // Each symbol is in a single chain: walking more steps than there are
+    // symbols means that a chain loops.
+    let mut steps = 0;
+    for i in 0..nbucket {
+        let mut len = 0;
+        let mut sym_idx = bucket(i)? as usize;
+        while sym_idx != 0 {
+            if steps == nchain {
+                bail!("the chain of bucket #{i} loops");
+            }
+            steps += 1;
+            len += 1;
+            sym_idx = chain(sym_idx)? as usize;
+        }
+        lengths.push(len);
+    }
+
+    let table = PairTable(18);
+    table.field("Buckets");
+    println!("{nbucket}");
+    table.field("Chains");
+    println!("{nchain}");
+    println!();
+    chain_histogram(&lengths);
+
+    Ok(())
+}
+
+pub fn gnu_hash_table(elf: &Elf, content: &[u8], lookup: Option<&str>) -> Result<()> {
+    let table = GnuHash::parse(elf, content)?;
+    let GnuHash { word_size, nbuckets, symoffset, bloom_size, bloom_shift, .. } = table;
+    let bloom = |i: usize| table.bloom(i);
+    let bucket = |i: usize| table.bucket(i);
+    let chain = |sym_idx: usize| table.chain(sym_idx);
+
+    if let Some(name) = lookup {
+        if nbuckets == 0 || bloom_size == 0 {
+            bail!("the hash table has no bucket");
+        }
+        let hash = gnu_hash(name.as_bytes());
+        let bits = word_size as u32 * 8;
+        let word = bloom((hash / bits) as usize % bloom_size)?;
+        let mask = (1 << (hash % bits)) | (1 << ((hash >> bloom_shift) % bits));
+        let idx = hash as usize % nbuckets;
+        print_lookup_header(name, hash, idx);
+
+        if word & mask != mask {
+            bail!("{name:?} isn't in the hash table, the bloom filter rejects it");
+        }
+
+        let mut sym_idx = bucket(idx)? as usize;
+        if sym_idx < symoffset {
+            bail!("{name:?} isn't in the hash table, its bucket is empty");
+        }
+        loop {
+            let chain_hash = chain(sym_idx)?;
+            if chain_hash | 1 == hash | 1 && print_candidate(elf, sym_idx, name) {
+                return Ok(());
+            }
+            if chain_hash & 1 == 1 {
+                break;
+            }
+            sym_idx += 1;
+        }
+        bail!("{name:?} isn't in the hash table");
+    }
+
+    let mut lengths = Vec::with_capacity(nbuckets.min(content.len() / 4));
+    for i in 0..nbuckets {
+        let mut len = 0;
+        let sym_idx = bucket(i)? as usize;
+        if sym_idx >= symoffset {
+            while chain(sym_idx + len)? & 1 == 0 {
+                len += 1;
+            }
+            len += 1;
+        }
+        lengths.push(len);
+    }
+
+    let mut bits_set = 0;
+    for i in 0..bloom_size {
+        bits_set += bloom(i)?.count_ones();
+    }
+    let bloom_bits = bloom_size * word_size * 8;
+
+    let table = PairTable(18);
+    table.field("Buckets");
+    println!("{nbuckets}");
+    table.field("Symbol offset");
+    println!("{symoffset}");
+    table.field("Bloom size");
+    println!("{bloom_size} word(s) of {} bits", word_size * 8);
+    table.field("Bloom shift");
+    println!("{bloom_shift}");
+    table.field("Bloom bits set");
+    println!(
+        "{bits_set} of {bloom_bits} ({:.1}%)",
+        bits_set as f64 * 100.0 / bloom_bits.max(1) as f64,
+    );
+    println!();
+    chain_histogram(&lengths);
+
+    Ok(())
+}
+
+fn print_lookup_header(name: &str, hash: u32, bucket: usize) {
+    print_header(&format!("LOOKUP {name:?}"));
+    let table = PairTable(18);
+    table.field("Hash");
+    println!("{hash:#010x}");
+    table.field("Bucket");
+    println!("{bucket}");
+}
+
+/// Print a dynamic symbol visited while walking a chain, and return whether it
+/// is the one looked up.
+fn print_candidate(elf: &Elf, sym_idx: usize, name: &str) -> bool {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+    let sym = elf.dynsyms.get(sym_idx);
+    let sym_name = sym.and_then(|sym| elf.dynstrtab.get_at(sym.st_name));
+    let found = sym_name == Some(name);
+
+    let table = PairTable(18);
+    table.field(&format!("Symbol #{sym_idx}"));
+    match (sym, sym_name) {
+        (Some(sym), Some(sym_name)) if found => println!(
+            "\x1b[32m{}\x1b[0m = {}", demangle(sym_name), sp.hex(sym.st_value),
+        ),
+        (Some(_), Some(sym_name)) => println!("\x1b[90m{}\x1b[0m", demangle(sym_name)),
+        _ => println!("\x1b[93m<invalid symbol>\x1b[0m"),
+    }
+
+    found
+}
+
+/// Print the distribution of chain lengths and, for each length, the share of
+/// the symbols found in at most as many steps.
+fn chain_histogram(lengths: &[usize]) {
+    let max = lengths.iter().copied().max().unwrap_or(0);
+    let nsyms: usize = lengths.iter().sum();
+    let mut counts = vec![0usize; max + 1];
+    for &len in lengths {
+        counts[len] += 1;
+    }
+    let nonempty = lengths.len() - counts[0];
+
+    let table = PairTable(18);
+    table.field("Empty buckets");
+    println!("{} of {}", counts[0], lengths.len());
+    table.field("Longest chain");
+    println!("{max}");
+    table.field("Average chain");
+    println!("{:.2}", nsyms as f64 / nonempty.max(1) as f64);
+    println!();
+
+    println!("\x1b[97mLength │ Buckets │      % │ Coverage │\x1b[0m");
+    println!("\x1b[97m{0:─<7}┼{0:─<9}┼{0:─<8}┼{0:─<10}┼{0:─<42}\x1b[0m", "");
+
+    let mut covered = 0;
+    let most = counts.iter().copied().max().unwrap_or(0).max(1);
+    for (len, &count) in counts.iter().enumerate() {
+        covered += len * count;
+        let pct = count as f64 * 100.0 / lengths.len().max(1) as f64;
+        let coverage = covered as f64 * 100.0 / nsyms.max(1) as f64;
+        println!(
+            "{len:6} \x1b[97m│\x1b[0m {count:7} \x1b[97m│\x1b[0m {pct:5.1}% \x1b[97m│\x1b[0m \
+             {coverage:7.1}% \x1b[97m│\x1b[0m \x1b[36m{:█<w$}\x1b[0m",
+            "", w = (count * 40).div_ceil(most),
+        );
+    }
+}
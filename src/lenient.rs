@@ -0,0 +1,271 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Parsing of truncated or corrupted ELFs, recovering as much as possible
+//! where goblin gives up on the first inconsistency.
+
+use anyhow::{Context, Result};
+use goblin::container::{Container, Ctx, Endian};
+use goblin::elf::{Dynamic, Elf, ProgramHeader, RelocSection, SectionHeader, Sym, Symtab};
+use goblin::elf::dynamic::{DT_RELA, DT_RPATH, DT_RUNPATH};
+use goblin::elf::program_header::{PT_DYNAMIC, PT_INTERP};
+use goblin::elf::section_header::{SHN_XINDEX, SHT_DYNSYM, SHT_NOBITS, SHT_REL, SHT_RELA,
+                                  SHT_SYMTAB};
+use goblin::elf::symver::{VerdefSection, VerneedSection, VersymSection};
+use goblin::strtab::Strtab;
+
+use crate::hash::{gnu_hash_layout, sysv_hash_layout};
+
+/// Parse what can be recovered of the ELF in `bytes`. Every piece that is
+/// missing or truncated is described in the returned notes.
+pub fn parse_lenient(bytes: &[u8]) -> Result<(Elf<'_>, Vec<String>)> {
+    let header = Elf::parse_header(bytes).context("couldn't parse the ELF header")?;
+    let mut elf = Elf::lazy_parse(header).context("couldn't parse the ELF header")?;
+    let ctx = Ctx::new(
+        if elf.is_64 { Container::Big } else { Container::Little },
+        Endian::from(elf.little_endian),
+    );
+    let mut notes = Vec::new();
+
+    let phnum = header.e_phnum as usize;
+    let count = fit_count(bytes, header.e_phoff, phnum, ProgramHeader::size(ctx));
+    if count < phnum {
+        notes.push(format!("only {count} of the {phnum} program headers are in the file"));
+    }
+    elf.program_headers = ProgramHeader::parse(bytes, header.e_phoff as usize, count, ctx)
+        .unwrap_or_default();
+    for (i, ph) in elf.program_headers.iter().enumerate() {
+        let avail = fit(bytes, ph.p_offset, ph.p_filesz);
+        if avail < ph.p_filesz {
+            notes.push(format!(
+                "segment #{i} is truncated: {avail:#x} of its {:#x} byte(s) are in the file",
+                ph.p_filesz,
+            ));
+        }
+    }
+
+    elf.interpreter = elf.program_headers.iter()
+        .find(|ph| ph.p_type == PT_INTERP && ph.p_filesz > 0)
+        .and_then(|ph| {
+            let end = ph.p_offset.checked_add(ph.p_filesz - 1)?;
+            bytes.get(ph.p_offset as usize..end as usize)
+        })
+        .and_then(|s| std::str::from_utf8(s).ok());
+
+    elf.section_headers = section_headers(bytes, &header, ctx, &mut notes);
+    let shdrs = &elf.section_headers;
+
+    let mut shstrndx = header.e_shstrndx as usize;
+    if shstrndx == SHN_XINDEX as usize {
+        shstrndx = shdrs.first().map_or(0, |sh| sh.sh_link as usize);
+    }
+    elf.shdr_strtab = strtab(bytes, shdrs.get(shstrndx), "the section name table", &mut notes);
+
+    for (i, sh) in shdrs.iter().enumerate() {
+        if sh.sh_type == SHT_NOBITS || sh.sh_size == 0 {
+            continue;
+        }
+        let avail = fit(bytes, sh.sh_offset, sh.sh_size);
+        if avail < sh.sh_size {
+            let name = elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("?");
+            notes.push(format!(
+                "section #{i} {name:?} is truncated: {avail:#x} of its {:#x} byte(s) are in \
+                 the file",
+                sh.sh_size,
+            ));
+        }
+    }
+
+    if let Some(sh) = shdrs.iter().rfind(|sh| sh.sh_type == SHT_SYMTAB) {
+        elf.syms = symtab(bytes, sh.sh_offset, sh.sh_size, ctx, "the symbol table", &mut notes);
+        elf.strtab = strtab(
+            bytes, shdrs.get(sh.sh_link as usize), "the symbol string table", &mut notes,
+        );
+    }
+
+    // The dynamic section is read as far as the file goes.
+    let phdrs: Vec<ProgramHeader> = elf.program_headers.iter()
+        .map(|ph| {
+            let mut ph = ph.clone();
+            if ph.p_type == PT_DYNAMIC {
+                ph.p_filesz = fit(bytes, ph.p_offset, ph.p_filesz);
+            }
+            ph
+        })
+        .collect();
+    elf.dynamic = Dynamic::parse(bytes, &phdrs, ctx).unwrap_or_else(|e| {
+        notes.push(format!("couldn't parse the dynamic section: {e}"));
+        None
+    });
+    if let Some(dynamic) = elf.dynamic.take() {
+        dynamic_tables(&mut elf, bytes, &dynamic, ctx, &mut notes);
+        elf.dynamic = Some(dynamic);
+    }
+
+    for (i, sh) in elf.section_headers.iter().enumerate() {
+        let is_rela = sh.sh_type == SHT_RELA;
+        if is_rela || sh.sh_type == SHT_REL {
+            let relocs = relocs(bytes, sh.sh_offset as usize, sh.sh_size as usize, is_rela, ctx);
+            elf.shdr_relocs.push((i, relocs));
+        }
+    }
+
+    elf.versym = VersymSection::parse(bytes, &elf.section_headers, ctx).ok().flatten();
+    elf.verdef = VerdefSection::parse(bytes, &elf.section_headers, ctx).ok().flatten();
+    elf.verneed = VerneedSection::parse(bytes, &elf.section_headers, ctx).ok().flatten();
+
+    Ok((elf, notes))
+}
+
+fn section_headers(
+    bytes: &[u8],
+    header: &goblin::elf::Header,
+    ctx: Ctx,
+    notes: &mut Vec<String>,
+) -> Vec<SectionHeader> {
+    let shnum = header.e_shnum as usize;
+    let shoff = header.e_shoff;
+
+    // With no count in the header, it is in the first section header.
+    let count = if shnum == 0 {
+        match SectionHeader::parse(bytes, shoff as usize, 0, ctx) {
+            Ok(shdrs) => return shdrs,
+            Err(e) => {
+                notes.push(format!("couldn't parse the section headers: {e}"));
+                return Vec::new();
+            },
+        }
+    } else {
+        fit_count(bytes, shoff, shnum, SectionHeader::size(ctx))
+    };
+
+    if count < shnum {
+        notes.push(format!("only {count} of the {shnum} section headers are in the file"));
+    }
+    if count == 0 {
+        return Vec::new();
+    }
+    SectionHeader::parse(bytes, shoff as usize, count, ctx).unwrap_or_default()
+}
+
+fn dynamic_tables<'a>(
+    elf: &mut Elf<'a>,
+    bytes: &'a [u8],
+    dynamic: &Dynamic,
+    ctx: Ctx,
+    notes: &mut Vec<String>,
+) {
+    let info = &dynamic.info;
+
+    let strsz = fit(bytes, info.strtab as u64, info.strsz as u64);
+    if strsz < info.strsz as u64 {
+        notes.push("the dynamic string table is truncated".to_string());
+    }
+    elf.dynstrtab = Strtab::parse(bytes, info.strtab, strsz as usize, 0).unwrap_or_default();
+    if info.soname != 0 {
+        elf.soname = elf.dynstrtab.get_at(info.soname);
+    }
+    elf.libraries = dynamic.get_libraries(&elf.dynstrtab);
+    for d in &dynamic.dyns {
+        let path = || elf.dynstrtab.get_at(d.d_val as usize);
+        match d.d_tag {
+            DT_RPATH => elf.rpaths.extend(path()),
+            DT_RUNPATH => elf.runpaths.extend(path()),
+            _ => (),
+        }
+    }
+
+    elf.dynrelas = relocs(bytes, info.rela, info.relasz, true, ctx);
+    elf.dynrels = relocs(bytes, info.rel, info.relsz, false, ctx);
+    elf.pltrelocs = relocs(bytes, info.jmprel, info.pltrelsz, info.pltrel == DT_RELA, ctx);
+
+    // The dynamic symbols are counted from the hash tables, from their
+    // section, or from the relocations referring to them.
+    let syment = Sym::size(ctx.container) as u64;
+    let table = |off: Option<u64>| bytes.get((off? as usize)..);
+    let from_hash = table(info.gnu_hash)
+        .and_then(|content| gnu_hash_layout(elf, content).ok())
+        .or_else(|| table(info.hash).and_then(|content| sysv_hash_layout(elf, content).ok()))
+        .map_or(0, |(count, _)| count);
+    let from_section = elf.section_headers.iter()
+        .find(|sh| sh.sh_type == SHT_DYNSYM)
+        .map_or(0, |sh| sh.sh_size / syment);
+    let from_relocs = elf.dynrelas.iter()
+        .chain(elf.dynrels.iter())
+        .chain(elf.pltrelocs.iter())
+        .map(|r| r.r_sym as u64 + 1)
+        .max()
+        .unwrap_or(0);
+    let count = from_hash.max(from_section).max(from_relocs);
+    elf.dynsyms = symtab(
+        bytes, info.symtab as u64, count * syment, ctx, "the dynamic symbol table", notes,
+    );
+}
+
+fn strtab<'a>(
+    bytes: &'a [u8],
+    sh: Option<&SectionHeader>,
+    what: &str,
+    notes: &mut Vec<String>,
+) -> Strtab<'a> {
+    let Some(sh) = sh else {
+        notes.push(format!("{what} is missing"));
+        return Strtab::default();
+    };
+
+    let size = fit(bytes, sh.sh_offset, sh.sh_size);
+    if size < sh.sh_size {
+        notes.push(format!("{what} is truncated, some names are missing"));
+    }
+    Strtab::parse(bytes, sh.sh_offset as usize, size as usize, 0).unwrap_or_default()
+}
+
+fn symtab<'a>(
+    bytes: &'a [u8],
+    offset: u64,
+    size: u64,
+    ctx: Ctx,
+    what: &str,
+    notes: &mut Vec<String>,
+) -> Symtab<'a> {
+    let syment = Sym::size(ctx.container);
+    let count = (size / syment as u64) as usize;
+    let avail = fit_count(bytes, offset, count, syment);
+    if avail < count {
+        notes.push(format!("only {avail} of the {count} entries of {what} are in the file"));
+    }
+    Symtab::parse(bytes, offset as usize, avail, ctx).unwrap_or_default()
+}
+
+fn relocs(
+    bytes: &[u8],
+    offset: usize,
+    size: usize,
+    is_rela: bool,
+    ctx: Ctx,
+) -> RelocSection<'_> {
+    let size = fit(bytes, offset as u64, size as u64) as usize;
+    RelocSection::parse(bytes, offset, size, is_rela, ctx).unwrap_or_default()
+}
+
+/// How many of the `size` bytes at `offset` are in the file.
+fn fit(bytes: &[u8], offset: u64, size: u64) -> u64 {
+    (bytes.len() as u64).saturating_sub(offset).min(size)
+}
+
+/// How many of the `count` entries of `entsize` bytes at `offset` are in the
+/// file.
+fn fit_count(bytes: &[u8], offset: u64, count: usize, entsize: usize) -> usize {
+    if offset == 0 || entsize == 0 {
+        return 0;
+    }
+    let avail = (bytes.len() as u64).saturating_sub(offset) / entsize as u64;
+    count.min(avail as usize)
+}
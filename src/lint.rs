@@ -0,0 +1,355 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Structural checks of the ELF, for malformed or suspicious files.
+
+use anyhow::{bail, Result};
+use goblin::elf::{Elf, SectionHeader, Symtab};
+use goblin::elf::header::{ET_DYN, ET_EXEC, ET_REL};
+use goblin::elf::program_header::{PT_LOAD, PT_TLS};
+use goblin::elf::section_header::{SHF_TLS, SHN_ABS, SHN_COMMON, SHN_LORESERVE, SHN_UNDEF,
+                                  SHN_XINDEX, SHT_NOBITS, SHT_NULL};
+use goblin::elf::sym::{STT_FILE, STT_SECTION, STT_TLS};
+use goblin::strtab::Strtab;
+
+use crate::map::section_in_segment;
+use crate::print::print_header;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A problem found in the ELF. The identifier is stable, so that findings can
+/// be filtered or suppressed by tooling.
+struct Finding {
+    severity: Severity,
+    id: &'static str,
+    message: String,
+}
+
+#[derive(Default)]
+struct Findings(Vec<Finding>);
+
+impl Findings {
+    fn error(&mut self, id: &'static str, message: String) {
+        self.0.push(Finding { severity: Severity::Error, id, message });
+    }
+
+    fn warning(&mut self, id: &'static str, message: String) {
+        self.0.push(Finding { severity: Severity::Warning, id, message });
+    }
+
+    fn info(&mut self, id: &'static str, message: String) {
+        self.0.push(Finding { severity: Severity::Info, id, message });
+    }
+}
+
+pub fn lint(elf: &Elf, bytes: &[u8]) -> Result<()> {
+    let mut findings = Findings::default();
+
+    check_header(elf, bytes, &mut findings);
+    check_sections(elf, bytes, &mut findings);
+    check_segments(elf, bytes, &mut findings);
+    check_entry(elf, &mut findings);
+    check_symbols(elf, &elf.syms, &elf.strtab, ".symtab", &mut findings);
+    check_symbols(elf, &elf.dynsyms, &elf.dynstrtab, ".dynsym", &mut findings);
+
+    print_header("LINT");
+    for f in &findings.0 {
+        let sev = match f.severity {
+            Severity::Error => "\x1b[1;91merror  ",
+            Severity::Warning => "\x1b[93mwarning",
+            Severity::Info => "\x1b[94minfo   ",
+        };
+        println!("{sev}\x1b[0m \x1b[97m[{}]\x1b[0m {}", f.id, f.message);
+    }
+
+    let count = |sev| findings.0.iter().filter(|f| f.severity == sev).count();
+    let (errors, warnings, infos) =
+        (count(Severity::Error), count(Severity::Warning), count(Severity::Info));
+    if findings.0.is_empty() {
+        println!("\x1b[32mNo problem found.\x1b[0m");
+    } else {
+        println!();
+        println!("{errors} error(s), {warnings} warning(s), {infos} info(s)");
+    }
+
+    if errors > 0 {
+        bail!("the ELF is malformed");
+    }
+
+    Ok(())
+}
+
+fn check_header(elf: &Elf, bytes: &[u8], findings: &mut Findings) {
+    let h = &elf.header;
+    let len = bytes.len() as u64;
+
+    let ph_end = h.e_phoff.saturating_add(h.e_phnum as u64 * h.e_phentsize as u64);
+    if h.e_phnum > 0 && ph_end > len {
+        findings.error("phdrs-beyond-eof", format!(
+            "program headers end at {ph_end:#x}, past the end of the file ({len:#x})",
+        ));
+    }
+
+    let sh_end = h.e_shoff.saturating_add(h.e_shnum as u64 * h.e_shentsize as u64);
+    if h.e_shnum > 0 && sh_end > len {
+        findings.error("shdrs-beyond-eof", format!(
+            "section headers end at {sh_end:#x}, past the end of the file ({len:#x})",
+        ));
+    }
+
+    if h.e_shstrndx as u32 != SHN_UNDEF && h.e_shstrndx as usize >= elf.section_headers.len()
+        && h.e_shstrndx as u32 != SHN_XINDEX {
+        findings.error("shstrndx-out-of-range", format!(
+            "e_shstrndx is {}, but there are only {} section(s)",
+            h.e_shstrndx, elf.section_headers.len(),
+        ));
+    }
+
+    if h.e_type != ET_REL && h.e_phnum == 0 {
+        findings.warning("no-program-headers", "the ELF has no program header".to_string());
+    }
+}
+
+fn check_sections(elf: &Elf, bytes: &[u8], findings: &mut Findings) {
+    let len = bytes.len() as u64;
+    let shnum = elf.section_headers.len();
+    let name = |i: usize, sh: &SectionHeader| match elf.shdr_strtab.get_at(sh.sh_name) {
+        Some(name) => format!("section #{i} {name:?}"),
+        None => format!("section #{i}"),
+    };
+
+    for (i, sh) in elf.section_headers.iter().enumerate().skip(1) {
+        if elf.shdr_strtab.get_at(sh.sh_name).is_none() {
+            findings.error("section-name-out-of-bounds", format!(
+                "section #{i} has its name at {:#x}, outside of the section name table",
+                sh.sh_name,
+            ));
+        }
+
+        if sh.sh_type != SHT_NOBITS && sh.sh_type != SHT_NULL
+            && sh.sh_offset.checked_add(sh.sh_size).is_none_or(|end| end > len) {
+            findings.error("section-beyond-eof", format!(
+                "{} at {:#x} has a size of {:#x}, past the end of the file ({len:#x})",
+                name(i, sh), sh.sh_offset, sh.sh_size,
+            ));
+        }
+
+        if sh.sh_link as usize >= shnum {
+            findings.warning("section-bad-link", format!(
+                "{} links to section #{}, which doesn't exist", name(i, sh), sh.sh_link,
+            ));
+        }
+
+        if sh.sh_addralign > 1 {
+            if !sh.sh_addralign.is_power_of_two() {
+                findings.error("section-bad-align", format!(
+                    "{} has an alignment of {}, not a power of two",
+                    name(i, sh), sh.sh_addralign,
+                ));
+            } else if !sh.sh_addr.is_multiple_of(sh.sh_addralign) {
+                findings.warning("section-misaligned", format!(
+                    "{} is at {:#x}, not aligned to {}",
+                    name(i, sh), sh.sh_addr, sh.sh_addralign,
+                ));
+            }
+        }
+    }
+
+    // Sections' content in the file.
+    let mut in_file: Vec<(usize, &SectionHeader)> = elf.section_headers.iter()
+        .enumerate()
+        .filter(|(_, sh)| sh.sh_type != SHT_NOBITS && sh.sh_type != SHT_NULL && sh.sh_size > 0)
+        .collect();
+    in_file.sort_by_key(|(_, sh)| sh.sh_offset);
+    for pair in in_file.windows(2) {
+        let ((i, a), (j, b)) = (pair[0], pair[1]);
+        if a.sh_offset.saturating_add(a.sh_size) > b.sh_offset {
+            findings.error("section-file-overlap", format!(
+                "{} and {} overlap in the file at {:#x}",
+                name(i, a), name(j, b), b.sh_offset,
+            ));
+        }
+    }
+
+    // Sections' addresses in memory, `.tbss` occupies none outside of TLS.
+    let tbss = |sh: &SectionHeader| sh.sh_type == SHT_NOBITS && sh.sh_flags & SHF_TLS as u64 != 0;
+    let mut in_memory: Vec<(usize, &SectionHeader)> = elf.section_headers.iter()
+        .enumerate()
+        .filter(|(_, sh)| sh.is_alloc() && sh.sh_size > 0 && !tbss(sh))
+        .collect();
+    in_memory.sort_by_key(|(_, sh)| sh.sh_addr);
+    if elf.header.e_type != ET_REL {
+        for pair in in_memory.windows(2) {
+            let ((i, a), (j, b)) = (pair[0], pair[1]);
+            if a.sh_addr.saturating_add(a.sh_size) > b.sh_addr {
+                findings.error("section-addr-overlap", format!(
+                    "{} and {} overlap in memory at {:#x}",
+                    name(i, a), name(j, b), b.sh_addr,
+                ));
+            }
+        }
+    }
+
+    if !elf.program_headers.is_empty() {
+        for (i, sh) in in_memory {
+            let loaded = elf.program_headers.iter()
+                .any(|ph| ph.p_type == PT_LOAD && section_in_segment(sh, ph));
+            if !loaded {
+                findings.warning("section-not-loaded", format!(
+                    "{} is allocated, but isn't in any loadable segment", name(i, sh),
+                ));
+            }
+        }
+    }
+}
+
+fn check_segments(elf: &Elf, bytes: &[u8], findings: &mut Findings) {
+    let len = bytes.len() as u64;
+
+    for (i, ph) in elf.program_headers.iter().enumerate() {
+        if ph.p_offset.checked_add(ph.p_filesz).is_none_or(|end| end > len) {
+            findings.error("segment-beyond-eof", format!(
+                "segment #{i} at {:#x} has a file size of {:#x}, past the end of the file \
+                 ({len:#x})",
+                ph.p_offset, ph.p_filesz,
+            ));
+        }
+
+        if ph.p_type == PT_LOAD && ph.p_filesz > ph.p_memsz {
+            findings.error("segment-filesz-exceeds-memsz", format!(
+                "segment #{i} has {:#x} byte(s) in the file, but only {:#x} in memory",
+                ph.p_filesz, ph.p_memsz,
+            ));
+        }
+
+        if ph.p_align > 1 {
+            if !ph.p_align.is_power_of_two() {
+                findings.error("segment-bad-align", format!(
+                    "segment #{i} has an alignment of {:#x}, not a power of two", ph.p_align,
+                ));
+            } else if ph.p_type == PT_LOAD
+                && ph.p_offset % ph.p_align != ph.p_vaddr % ph.p_align {
+                findings.error("segment-misaligned", format!(
+                    "segment #{i} has p_offset {:#x} and p_vaddr {:#x}, which aren't \
+                     congruent modulo its alignment {:#x}",
+                    ph.p_offset, ph.p_vaddr, ph.p_align,
+                ));
+            }
+        }
+    }
+
+    let loads: Vec<(usize, _)> = elf.program_headers.iter()
+        .enumerate()
+        .filter(|(_, ph)| ph.p_type == PT_LOAD)
+        .collect();
+    if loads.windows(2).any(|pair| pair[0].1.p_vaddr > pair[1].1.p_vaddr) {
+        findings.warning("segment-unsorted", "loadable segments aren't sorted by virtual \
+                                              address".to_string());
+    }
+
+    let mut sorted = loads.clone();
+    sorted.sort_by_key(|(_, ph)| ph.p_vaddr);
+    for pair in sorted.windows(2) {
+        let ((i, a), (j, b)) = (pair[0], pair[1]);
+        if a.p_vaddr.saturating_add(a.p_memsz) > b.p_vaddr {
+            findings.error("segment-overlap", format!(
+                "loadable segments #{i} and #{j} overlap in memory at {:#x}", b.p_vaddr,
+            ));
+        }
+    }
+}
+
+fn check_entry(elf: &Elf, findings: &mut Findings) {
+    let entry = elf.entry;
+    if entry == 0 || !matches!(elf.header.e_type, ET_EXEC | ET_DYN) {
+        return;
+    }
+
+    let ph = elf.program_headers.iter()
+        .filter(|ph| ph.p_type == PT_LOAD)
+        .find(|ph| (ph.p_vaddr..ph.p_vaddr.saturating_add(ph.p_memsz)).contains(&entry));
+    match ph {
+        None => findings.error("entry-outside-segments", format!(
+            "the entry point {entry:#x} isn't in any loadable segment",
+        )),
+        Some(ph) if !ph.is_executable() => findings.error("entry-not-executable", format!(
+            "the entry point {entry:#x} is in a segment that isn't executable",
+        )),
+        Some(_) => (),
+    }
+}
+
+fn check_symbols(
+    elf: &Elf,
+    syms: &Symtab,
+    strtab: &Strtab,
+    table: &str,
+    findings: &mut Findings,
+) {
+    let tls = elf.program_headers.iter().find(|ph| ph.p_type == PT_TLS);
+    for (i, sym) in syms.iter().enumerate().skip(1) {
+        let Some(name) = strtab.get_at(sym.st_name) else {
+            findings.error("symbol-name-out-of-bounds", format!(
+                "{table} symbol #{i} has its name at {:#x}, outside of the string table",
+                sym.st_name,
+            ));
+            continue;
+        };
+        let shndx = sym.st_shndx as u32;
+        if shndx == SHN_UNDEF || shndx == SHN_ABS || shndx == SHN_COMMON || shndx == SHN_XINDEX {
+            continue;
+        }
+        if shndx >= SHN_LORESERVE {
+            findings.info("symbol-reserved-section", format!(
+                "{table} symbol {name:?} has the reserved section index {shndx:#x}",
+            ));
+            continue;
+        }
+        let Some(sh) = elf.section_headers.get(sym.st_shndx) else {
+            findings.error("symbol-bad-section-index", format!(
+                "{table} symbol {name:?} refers to section #{shndx}, which doesn't exist",
+            ));
+            continue;
+        };
+        if matches!(sym.st_type(), STT_SECTION | STT_FILE) {
+            continue;
+        }
+
+        // Relocatable symbols are offsets in their section, TLS ones in the
+        // TLS segment; a symbol may point right past the end of its section
+        // (e.g. `_end`).
+        let (start, end) = if elf.header.e_type == ET_REL {
+            (0, sh.sh_size)
+        } else if sym.st_type() == STT_TLS {
+            let Some(tls) = tls else {
+                continue;
+            };
+            let start = sh.sh_addr.saturating_sub(tls.p_vaddr);
+            (start, start.saturating_add(sh.sh_size))
+        } else if sh.is_alloc() {
+            (sh.sh_addr, sh.sh_addr.saturating_add(sh.sh_size))
+        } else {
+            continue;
+        };
+        let sym_end = sym.st_value.saturating_add(sym.st_size);
+        if sym.st_value < start || sym_end > end {
+            findings.warning("symbol-outside-section", format!(
+                "{table} symbol {name:?} spans {:#x}..{sym_end:#x}, outside of its section \
+                 {:?} ({start:#x}..{end:#x})",
+                sym.st_value, elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("?"),
+            ));
+        }
+    }
+}
@@ -0,0 +1,347 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Parsing of language-specific data areas (LSDA), found in
+//! `.gcc_except_table`, as emitted by GCC and LLVM for C++ and Rust.
+
+use goblin::container::Container;
+use goblin::elf::Elf;
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::eh::{DW_EH_PE_OMIT, encoding_name, PtrBases, read_encoded};
+use crate::elf::content_at_vaddr;
+use crate::print::SizePrint;
+use crate::sym::{addr_to_sym, sym_name_at};
+
+pub struct Lsda {
+    pub lpstart: u64,
+    pub ttype_enc: u8,
+    pub call_site_enc: u8,
+    pub call_sites: Vec<CallSite>,
+}
+
+pub struct CallSite {
+    pub start: u64,
+    pub len: u64,
+    pub landing_pad: Option<u64>,
+    pub actions: Vec<Action>,
+}
+
+pub enum Action {
+    Cleanup,
+    Catch(TypeInfo),
+    ExceptionSpec(Vec<TypeInfo>),
+}
+
+/// A reference to a type-info object in the types table. A null address means
+/// a catch-all clause.
+#[derive(Copy, Clone)]
+pub struct TypeInfo {
+    pub addr: u64,
+    pub indirect: bool,
+}
+
+pub fn parse_lsda<'a>(
+    elf: &'a Elf<'a>,
+    bytes: &'a [u8],
+    lsda_addr: u64,
+    func_start: u64,
+) -> Result<Lsda> {
+    let content = content_at_vaddr(elf, bytes, lsda_addr)
+        .ok_or_else(|| anyhow!("LSDA address is not backed by file content"))?;
+    let mut r = LsdaReader::new(elf, bytes, content, lsda_addr, func_start);
+
+    let lpstart_enc = r.u8()?;
+    let lpstart = if lpstart_enc == DW_EH_PE_OMIT {
+        func_start
+    } else {
+        r.encoded(lpstart_enc)?
+    };
+
+    let ttype_enc = r.u8()?;
+    let ttype_base = if ttype_enc == DW_EH_PE_OMIT {
+        None
+    } else {
+        let off = r.uleb128()? as usize;
+        Some(r.off.checked_add(off).ok_or_else(|| anyhow!("types table out of the LSDA"))?)
+    };
+
+    let call_site_enc = r.u8()?;
+    let table_len = r.uleb128()? as usize;
+    let table_end = r.off.checked_add(table_len)
+        .ok_or_else(|| anyhow!("call site table out of the LSDA"))?;
+    let action_table = table_end;
+
+    let mut call_sites = Vec::new();
+    while r.off < table_end {
+        let start = r.encoded(call_site_enc)?;
+        let len = r.encoded(call_site_enc)?;
+        let landing_pad = r.encoded(call_site_enc)?;
+        let action = r.uleb128()? as usize;
+
+        let actions = if action == 0 {
+            if landing_pad != 0 { vec![Action::Cleanup] } else { Vec::new() }
+        } else {
+            let off = action_table.checked_add(action - 1)
+                .ok_or_else(|| anyhow!("action {action} out of the LSDA"))?;
+            r.actions(off, ttype_enc, ttype_base)?
+        };
+
+        let out_of_range = || anyhow!("call site out of the address space");
+        call_sites.push(CallSite {
+            start: lpstart.checked_add(start).ok_or_else(out_of_range)?,
+            len,
+            landing_pad: match landing_pad {
+                0 => None,
+                lp => Some(lpstart.checked_add(lp).ok_or_else(out_of_range)?),
+            },
+            actions,
+        });
+    }
+
+    Ok(Lsda { lpstart, ttype_enc, call_site_enc, call_sites })
+}
+
+pub fn print_lsda(elf: &Elf, bytes: &[u8], lsda: &Lsda, prefix: &str) {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+
+    println!("{prefix}├╴ LPStart = {}", sp.hex(lsda.lpstart));
+    println!(
+        "{prefix}├╴ Call sites encoding = {:#04x} {}",
+        lsda.call_site_enc, encoding_name(lsda.call_site_enc),
+    );
+    println!(
+        "{prefix}├╴ Types encoding = {:#04x} {}",
+        lsda.ttype_enc, encoding_name(lsda.ttype_enc),
+    );
+
+    for cs in &lsda.call_sites {
+        print!(
+            "{prefix}├╴ \x1b[97mCall site\x1b[0m {}..{}",
+            sp.hex(cs.start), sp.hex(cs.start.saturating_add(cs.len)),
+        );
+        match cs.landing_pad {
+            Some(lp) => println!("  ⮞  landing pad {}", addr_with_sym(elf, lp)),
+            None => println!("  \x1b[90m(no landing pad)\x1b[0m"),
+        }
+
+        for action in &cs.actions {
+            print!("{prefix}│    └╴ ");
+            match action {
+                Action::Cleanup => println!("\x1b[36mcleanup\x1b[0m"),
+                Action::Catch(ti) => {
+                    println!("\x1b[32mcatch\x1b[0m ({})", type_name(elf, bytes, ti));
+                },
+                Action::ExceptionSpec(tis) => {
+                    let names = tis.iter()
+                        .map(|ti| type_name(elf, bytes, ti))
+                        .collect::<Vec<_>>();
+                    println!("\x1b[33mthrow\x1b[0m ({})", names.join(", "));
+                },
+            }
+        }
+    }
+}
+
+fn addr_with_sym(elf: &Elf, addr: u64) -> String {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+
+    match addr_to_sym(&elf.syms, addr) {
+        Some(sym) => {
+            let name = elf.strtab.get_at(sym.st_name).unwrap_or("???");
+            let name = crate::demangle::demangle(name).to_string();
+            format!("{} ({name} + {:#x})", sp.hex(addr), addr - sym.st_value)
+        },
+        None => sp.hex(addr).to_string(),
+    }
+}
+
+fn type_name(elf: &Elf, bytes: &[u8], ti: &TypeInfo) -> String {
+    if ti.addr == 0 {
+        return "...".to_string();
+    }
+
+    let name = if ti.indirect {
+        sym_name_at(elf, ti.addr)
+            .map(|n| n.trim_start_matches("DW.ref."))
+            .or_else(|| {
+                let ptr = read_ptr(elf, bytes, ti.addr)?;
+                sym_name_at(elf, ptr)
+            })
+    } else {
+        sym_name_at(elf, ti.addr)
+    };
+
+    match name {
+        Some(name) => format!("type info {name}"),
+        None => {
+            let sp = SizePrint::new(elf.header.container().unwrap_or(Container::Big));
+            format!("type info at {}", sp.hex(ti.addr))
+        },
+    }
+}
+
+/// Read the non-null pointer stored at `vaddr`.
+pub fn read_ptr(elf: &Elf, bytes: &[u8], vaddr: u64) -> Option<u64> {
+    let content = content_at_vaddr(elf, bytes, vaddr)?;
+    let (_, _, ptr) = read_encoded(
+        elf, bytes, 0x00, content, vaddr, &PtrBases::default(),
+    ).ok()?;
+
+    (ptr != 0).then_some(ptr)
+}
+
+struct LsdaReader<'a> {
+    elf: &'a Elf<'a>,
+    bytes: &'a [u8],
+    data: &'a [u8],
+    off: usize,
+    vaddr: u64,
+    bases: PtrBases,
+}
+
+impl<'a> LsdaReader<'a> {
+    fn new(
+        elf: &'a Elf<'a>,
+        bytes: &'a [u8],
+        data: &'a [u8],
+        vaddr: u64,
+        func_start: u64,
+    ) -> Self {
+        Self {
+            elf,
+            bytes,
+            data,
+            off: 0,
+            vaddr,
+            bases: PtrBases {
+                func: Some(func_start),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        let b = *self.data.get(self.off)
+            .ok_or_else(|| anyhow!("unexpected end of LSDA"))?;
+        self.off += 1;
+        Ok(b)
+    }
+
+    fn uleb128(&mut self) -> Result<u64> {
+        self.encoded(0x01)
+    }
+
+    fn sleb128(&mut self) -> Result<i64> {
+        Ok(self.encoded(0x09)? as i64)
+    }
+
+    fn enc_size(&self, enc: u8) -> Result<usize> {
+        Ok(match enc & 0x0f {
+            0x00 => if self.elf.is_64 { 8 } else { 4 },
+            0x02 | 0x0a => 2,
+            0x03 | 0x0b => 4,
+            0x04 | 0x0c => 8,
+            _ => bail!("encoding {enc:#04x} has no fixed size"),
+        })
+    }
+
+    /// Read a pointer with the given `DW_EH_PE_*` encoding. The indirect bit
+    /// is ignored, it is up to the caller to dereference the pointer.
+    fn encoded(&mut self, enc: u8) -> Result<u64> {
+        let (size, _, value) = read_encoded(
+            self.elf,
+            self.bytes,
+            enc & 0x7f,
+            self.data.get(self.off..).unwrap_or(&[]),
+            self.vaddr.wrapping_add(self.off as u64),
+            &self.bases,
+        ).context("malformed LSDA")?;
+        self.off += size;
+
+        Ok(value)
+    }
+
+    fn actions(
+        &mut self,
+        mut off: usize,
+        ttype_enc: u8,
+        ttype_base: Option<usize>,
+    ) -> Result<Vec<Action>> {
+        let resume = self.off;
+        let mut actions = Vec::new();
+
+        loop {
+            self.off = off;
+            let filter = self.sleb128()?;
+            let next_pos = self.off;
+            let next = self.sleb128()?;
+
+            actions.push(match filter {
+                0 => Action::Cleanup,
+                f if f > 0 => Action::Catch(
+                    self.type_info(f as u64, ttype_enc, ttype_base)?
+                ),
+                f => {
+                    let base = ttype_base
+                        .ok_or_else(|| anyhow!("LSDA has no types table"))?;
+                    // `f` is negative, so `-(f + 1)` can't overflow.
+                    self.off = base.checked_add(-(f + 1) as usize)
+                        .ok_or_else(|| anyhow!("exception spec {f} out of the LSDA"))?;
+                    let mut spec = Vec::new();
+                    loop {
+                        let idx = self.uleb128()?;
+                        if idx == 0 {
+                            break;
+                        }
+                        let pos = self.off;
+                        spec.push(self.type_info(idx, ttype_enc, ttype_base)?);
+                        self.off = pos;
+                    }
+                    Action::ExceptionSpec(spec)
+                },
+            });
+
+            if next == 0 {
+                break;
+            }
+            off = next_pos.checked_add_signed(next as isize)
+                .ok_or_else(|| anyhow!("next action out of the LSDA"))?;
+            if actions.len() > 1024 {
+                bail!("LSDA action chain is too long");
+            }
+        }
+
+        self.off = resume;
+        Ok(actions)
+    }
+
+    fn type_info(
+        &mut self,
+        index: u64,
+        ttype_enc: u8,
+        ttype_base: Option<usize>,
+    ) -> Result<TypeInfo> {
+        let base = ttype_base
+            .ok_or_else(|| anyhow!("LSDA has no types table"))?;
+        let size = self.enc_size(ttype_enc)?;
+        self.off = usize::try_from(index).ok()
+            .and_then(|index| index.checked_mul(size))
+            .and_then(|delta| base.checked_sub(delta))
+            .ok_or_else(|| anyhow!("type index {index} out of the LSDA"))?;
+
+        Ok(TypeInfo {
+            addr: self.encoded(ttype_enc)?,
+            indirect: ttype_enc & 0x80 != 0,
+        })
+    }
+}
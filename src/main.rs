@@ -16,12 +16,26 @@ use memmap2::Mmap;
 use anyhow::{anyhow, bail, Context, Result};
 
 use crate::args::{Options, Command, SymbolsArgs};
+use crate::callgraph::callgraph;
+use crate::debugdata::{merge_symbols, mini_debug_info};
+use crate::demangle::set_demangle_mode;
+use crate::dump::dump;
 use crate::eh::eh;
-use crate::func::do_fn;
+use crate::export::export;
+use crate::func::{do_disasm, do_fn};
+use crate::grep::grep;
 use crate::header::{header, program_headers};
+use crate::lenient::parse_lenient;
+use crate::lint::lint;
+use crate::map::memory_map;
+use crate::obj::do_obj;
+use crate::patch::patch;
 use crate::print::{PairTable, print_header, SizePrint};
+use crate::recover::recover_sections;
 use crate::sections::{all_sections, one_section};
+use crate::strings::strings;
 use crate::sym::all_symbols;
+use crate::xrefs::xrefs;
 
 mod args;
 mod print;
@@ -31,6 +45,24 @@ mod sym;
 mod header;
 mod elf;
 mod eh;
+mod lsda;
+mod flow;
+mod xrefs;
+mod callgraph;
+mod grep;
+mod strings;
+mod dump;
+mod obj;
+mod asm;
+mod patch;
+mod export;
+mod map;
+mod lint;
+mod lenient;
+mod recover;
+mod debugdata;
+mod demangle;
+mod hash;
 
 fn main() {
     let args = Options::parse();
@@ -42,6 +74,8 @@ fn main() {
 }
 
 fn run(args: &Options) -> Result<()> {
+    set_demangle_mode(args.demangle);
+
     let elf_path = args.elf.clone().or_else(
         || std::env::var_os("ELF").map(|s| s.into())
     ).ok_or_else(
@@ -56,23 +90,78 @@ fn run(args: &Options) -> Result<()> {
         .with_context(|| format!("{}: couldn't mmap ELF", elf_path.display()))?;
     let bytes = &*map;
 
-    let obj = Object::parse(bytes)
-        .with_context(||
-            format!("{}: failed to parse ELF", elf_path.display())
-        )?;
-    let elf = match obj {
-        Object::Elf(elf) => Box::new(elf),
-        _ => bail!("{}: unsupported ELF format", elf_path.display()),
+    let mut elf = match Object::parse(bytes) {
+        Ok(Object::Elf(elf)) => Box::new(elf),
+        Ok(_) => bail!("{}: unsupported ELF format", elf_path.display()),
+        Err(e) if args.lenient => {
+            eprintln!("\x1b[93mWarning: {}: {e}\x1b[0m", elf_path.display());
+            let (elf, notes) = parse_lenient(bytes)
+                .with_context(|| format!("{}: failed to parse ELF", elf_path.display()))?;
+            for note in notes {
+                eprintln!("\x1b[93mWarning: {note}\x1b[0m");
+            }
+            eprintln!();
+            Box::new(elf)
+        },
+        Err(e) => return Err(e).with_context(||
+            format!("{}: failed to parse ELF (try `--lenient`)", elf_path.display())
+        ),
+    };
+
+    if args.recover_sections {
+        if elf.section_headers.is_empty() {
+            let count = recover_sections(&mut elf, bytes);
+            eprintln!(
+                "\x1b[93mWarning: the ELF has no section header, {count} synthetic section(s) \
+                 were recovered from the program headers\x1b[0m",
+            );
+            eprintln!();
+        } else {
+            eprintln!("\x1b[93mWarning: the ELF has section headers, none were recovered\x1b[0m");
+        }
+    }
+
+    let command = args.command.as_ref().unwrap_or(&Command::Summary);
+    // Only commands looking symbols up need those embedded in `.gnu_debugdata`.
+    let uses_symbols = matches!(
+        command,
+        Command::Symbols(_) | Command::Fn(_) | Command::Disasm(_) | Command::Xrefs(_)
+            | Command::Callgraph(_) | Command::Grep(_) | Command::Strings(_) | Command::Dump(_)
+            | Command::Obj(_) | Command::Patch(_) | Command::Eh(_),
+    );
+    let debugdata = if uses_symbols {
+        mini_debug_info(&elf, bytes).unwrap_or_else(|e| {
+            eprintln!("\x1b[93mWarning: {e:#}\x1b[0m");
+            None
+        })
+    } else {
+        None
     };
+    if let Some(ref embedded) = debugdata {
+        if let Err(e) = merge_symbols(&mut elf, embedded) {
+            eprintln!("\x1b[93mWarning: {e:#}\x1b[0m");
+        }
+    }
 
-    match args.command.as_ref().unwrap_or(&Command::Summary) {
+    match command {
         Command::Summary => summary(&elf),
         Command::Header => header(&elf),
         Command::ProgramHeader => program_headers(&elf),
         Command::Sections => all_sections(&elf),
+        Command::Map => memory_map(&elf),
+        Command::Lint => lint(&elf, bytes)?,
         Command::Section(opts) => one_section(&elf, bytes, opts)?,
         Command::Symbols(opts) => all_symbols(&elf, opts),
         Command::Fn(opts) => do_fn(&elf, bytes, opts)?,
+        Command::Disasm(opts) => do_disasm(&elf, bytes, opts)?,
+        Command::Xrefs(opts) => xrefs(&elf, bytes, opts)?,
+        Command::Callgraph(opts) => callgraph(&elf, bytes, opts)?,
+        Command::Grep(opts) => grep(&elf, bytes, opts)?,
+        Command::Strings(opts) => strings(&elf, bytes, opts)?,
+        Command::Dump(opts) => dump(&elf, bytes, opts)?,
+        Command::Obj(opts) => do_obj(&elf, bytes, opts)?,
+        Command::Patch(opts) => patch(&elf, bytes, &elf_path, opts)?,
+        Command::Export(opts) => export(&elf, bytes, opts)?,
         Command::Eh(opts) => eh(&elf, bytes, opts.clone())?,
         _ => todo!(),
     }
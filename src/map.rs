@@ -0,0 +1,227 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Memory map of the loadable segments and the sections they contain.
+
+use goblin::container::Container;
+use goblin::elf::{Elf, ProgramHeader, SectionHeader};
+use goblin::elf::program_header::{pt_to_str, PT_LOAD, PT_TLS};
+use goblin::elf::section_header::{SHF_TLS, SHT_NOBITS, SHT_NULL};
+
+use crate::print::{BinSize, print_header, SizePrint};
+use crate::sections::section_type;
+
+pub fn memory_map(elf: &Elf) {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+    let indent = match container {
+        Container::Big => 19,
+        Container::Little => 11,
+    };
+
+    let mut loads: Vec<(usize, &ProgramHeader)> = elf.program_headers.iter()
+        .enumerate()
+        .filter(|(_, ph)| ph.p_type == PT_LOAD)
+        .collect();
+    loads.sort_by_key(|(_, ph)| ph.p_vaddr);
+
+    print_header("MEMORY MAP");
+    if loads.is_empty() {
+        println!("\x1b[90mNo loadable segment.\x1b[0m");
+    }
+
+    let mut prev_end: Option<u64> = None;
+    for &(i, ph) in &loads {
+        let end = ph.p_vaddr.saturating_add(ph.p_memsz);
+        match prev_end {
+            Some(prev) if prev < ph.p_vaddr => {
+                let gap = ph.p_vaddr - prev;
+                println!(
+                    "{:indent$}   \x1b[90m░ gap of {gap:#x} ({})\x1b[0m",
+                    "", BinSize(gap),
+                );
+            },
+            Some(prev) if prev > ph.p_vaddr => {
+                println!(
+                    "{:indent$}   \x1b[91m▓ overlaps the previous segment by {:#x}\x1b[0m",
+                    "", prev - ph.p_vaddr,
+                );
+            },
+            _ => (),
+        }
+        prev_end = Some(prev_end.map_or(end, |prev| prev.max(end)));
+
+        print!(
+            "{} \x1b[97m┌── LOAD #{i}\x1b[0m  {}{}{}  file {:#x}..{:#x}",
+            sp.hex(ph.p_vaddr),
+            if ph.is_read() { "r" } else { "-" },
+            if ph.is_write() { "w" } else { "-" },
+            if ph.is_executable() { "x" } else { "-" },
+            ph.p_offset, ph.p_offset.saturating_add(ph.p_filesz),
+        );
+        print_alignment(ph);
+        println!();
+        if ph.p_vaddr.checked_add(ph.p_memsz).is_none() {
+            println!(
+                "{:indent$} \x1b[97m│\x1b[0m   \x1b[91m▓ invalid: its size of {:#x} goes past the \
+                 end of the address space\x1b[0m",
+                "", ph.p_memsz,
+            );
+        }
+
+        let mut sections: Vec<&SectionHeader> = elf.section_headers.iter()
+            .filter(|sh| sh.is_alloc() && section_in_segment(sh, ph))
+            .collect();
+        sections.sort_by_key(|sh| sh.sh_addr);
+
+        let mut cursor = ph.p_vaddr;
+        for sh in sections {
+            if sh.sh_addr > cursor && cursor == ph.p_vaddr && ph.p_offset == 0 {
+                println!(
+                    "{} \x1b[97m│\x1b[0m   \x1b[90m░ ELF header and program headers, {:#x} ({})\x1b[0m",
+                    sp.hex(cursor), sh.sh_addr - cursor, BinSize(sh.sh_addr - cursor),
+                );
+            } else if sh.sh_addr > cursor {
+                print_padding(&sp, cursor, sh.sh_addr - cursor);
+            }
+
+            let name = elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("?");
+            let (type_c, type_n) = section_type(sh.sh_type);
+            print!(
+                "{} \x1b[97m│\x1b[0m   {name:20} \x1b[{type_c}{type_n:12}\x1b[0m {:>#10x} {:>10}",
+                sp.hex(sh.sh_addr), sh.sh_size, BinSize(sh.sh_size),
+            );
+            match sh.sh_addr.checked_add(sh.sh_size) {
+                Some(sh_end) => cursor = cursor.max(sh_end),
+                None => {
+                    print!("  \x1b[91m▓ invalid: goes past the end of the address space\x1b[0m");
+                    cursor = u64::MAX;
+                },
+            }
+            println!();
+        }
+        if cursor < end {
+            print_padding(&sp, cursor, end - cursor);
+        }
+
+        if ph.p_memsz > ph.p_filesz {
+            println!(
+                "{:indent$} \x1b[97m│\x1b[0m   \x1b[90mfile content ends at {}, \
+                 the last {:#x} byte(s) are zero-filled\x1b[0m",
+                "", sp.hex(ph.p_vaddr.saturating_add(ph.p_filesz)), ph.p_memsz - ph.p_filesz,
+            );
+        }
+        println!("{} \x1b[97m└──\x1b[0m", sp.hex(end));
+    }
+
+    println!();
+    section_to_segment(elf);
+}
+
+/// Whether a section's content lies within a segment, following the same
+/// rules as `readelf -l`: allocated sections by virtual address, others by
+/// file offset, and `.tbss` only in the TLS segment.
+pub fn section_in_segment(sh: &SectionHeader, ph: &ProgramHeader) -> bool {
+    if sh.sh_type == SHT_NULL {
+        return false;
+    }
+    let tbss = sh.sh_type == SHT_NOBITS && sh.sh_flags & SHF_TLS as u64 != 0;
+    if tbss && ph.p_type != PT_TLS {
+        return false;
+    }
+    if !sh.is_alloc() && ph.p_type == PT_LOAD {
+        return false;
+    }
+
+    let within = |start: u64, size: u64, seg_start: u64, seg_size: u64| {
+        if start < seg_start {
+            return false;
+        }
+        let rel = start - seg_start;
+        if size == 0 {
+            rel < seg_size || (rel == 0 && seg_size == 0)
+        } else {
+            rel.checked_add(size).is_some_and(|end| end <= seg_size)
+        }
+    };
+
+    if sh.sh_type != SHT_NOBITS
+        && !within(sh.sh_offset, sh.sh_size, ph.p_offset, ph.p_filesz) {
+        return false;
+    }
+    if sh.is_alloc() {
+        return within(sh.sh_addr, sh.sh_size, ph.p_vaddr, ph.p_memsz);
+    }
+
+    true
+}
+
+fn print_alignment(ph: &ProgramHeader) {
+    if ph.p_align <= 1 {
+        return;
+    }
+
+    let shift = 64 - ph.p_align.leading_zeros() - 1;
+    if ph.p_offset % ph.p_align == ph.p_vaddr % ph.p_align {
+        print!("  align 2^{shift} \x1b[32m(offset ≡ vaddr)\x1b[0m");
+    } else {
+        print!(
+            "  align 2^{shift} \x1b[91m(offset {:#x} ≢ vaddr {:#x})\x1b[0m",
+            ph.p_offset % ph.p_align, ph.p_vaddr % ph.p_align,
+        );
+    }
+}
+
+fn print_padding(sp: &SizePrint, addr: u64, size: u64) {
+    println!(
+        "{} \x1b[97m│\x1b[0m   \x1b[90m░ padding of {size:#x} ({})\x1b[0m",
+        sp.hex(addr), BinSize(size),
+    );
+}
+
+fn section_to_segment(elf: &Elf) {
+    print_header("SECTION TO SEGMENT MAPPING");
+
+    println!("\x1b[97m{:>2} │ {:12} │ Sections\x1b[0m", "No", "Type");
+    println!("\x1b[97m{0:─<3}┼{0:─<14}┼{0:─<40}\x1b[0m", "");
+
+    let mut mapped = vec![false; elf.section_headers.len()];
+    for (i, ph) in elf.program_headers.iter().enumerate() {
+        let typ = match pt_to_str(ph.p_type) {
+            "UNKNOWN_PT" => "[unknown]",
+            s => s.strip_prefix("PT_").unwrap_or(s),
+        };
+        print!("{i:2} \x1b[97m│\x1b[0m {typ:12} \x1b[97m│\x1b[0m");
+
+        for (j, sh) in elf.section_headers.iter().enumerate() {
+            if section_in_segment(sh, ph) {
+                mapped[j] = true;
+                print!(" {}", elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("?"));
+            }
+        }
+        println!();
+    }
+
+    // Allocated sections outside of any segment are never loaded, which is
+    // most likely a mistake in the linker script.
+    print!("\x1b[90m{:>2}\x1b[0m \x1b[97m│\x1b[0m {:12} \x1b[97m│\x1b[0m", "--", "None");
+    for (j, sh) in elf.section_headers.iter().enumerate().skip(1) {
+        if mapped[j] {
+            continue;
+        }
+        let name = elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("?");
+        if sh.is_alloc() && !elf.program_headers.is_empty() {
+            print!(" \x1b[1;91m{name}\x1b[0m");
+        } else {
+            print!(" \x1b[90m{name}\x1b[0m");
+        }
+    }
+    println!();
+}
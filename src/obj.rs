@@ -0,0 +1,894 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Content of data symbols, decoded with the DWARF type of their variable.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use anyhow::{anyhow, bail, Result};
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use goblin::container::Container;
+use goblin::elf::Elf;
+use goblin::elf::header::ET_REL;
+use goblin::elf::section_header::{SHF_COMPRESSED, SHN_COMMON, SHT_NOBITS};
+use goblin::elf::sym::{Sym, STT_FUNC, STT_SECTION};
+use gimli::{AttributeValue, DwAt, DebuggingInformationEntry, Dwarf, EndianSlice, Operation,
+            RunTimeEndian, Unit, UnitOffset};
+
+use crate::args::ObjArgs;
+use crate::demangle::demangle;
+use crate::dump::hexdump_region;
+use crate::elf::{content_at_vaddr, find_symbol, vaddr_file_offset};
+use crate::print::{BinSize, PairTable, print_header, SizePrint};
+use crate::sections::find_section;
+
+type R<'a> = EndianSlice<'a, RunTimeEndian>;
+
+/// Arrays longer than this are truncated.
+const MAX_ELEMS: usize = 64;
+/// Arrays of scalars up to this length are printed on a single line.
+const MAX_INLINE: usize = 16;
+const MAX_DEPTH: usize = 32;
+/// Bytes not stored in the file are zero-filled up to this size, as the size
+/// of an object may come from untrusted DWARF.
+const MAX_ZEROED: u64 = 1 << 20;
+
+pub fn do_obj(elf: &Elf, bytes: &[u8], args: &ObjArgs) -> Result<()> {
+    let name = &args.name;
+    let (sym, strtab) = find_symbol(&elf.syms, &elf.strtab, name)
+        .filter(|sym| sym.st_shndx != 0)
+        .map(|sym| (sym, &elf.strtab))
+        .or_else(|| {
+            find_symbol(&elf.dynsyms, &elf.dynstrtab, name)
+                .filter(|sym| sym.st_shndx != 0)
+                .map(|sym| (sym, &elf.dynstrtab))
+        })
+        .ok_or_else(|| anyhow!("couldn't find any symbol matching {name:?}"))?;
+    if sym.st_type() == STT_FUNC {
+        bail!("{name:?} is a function, use `elf fn` to disassemble it");
+    }
+    let raw_name = strtab.get_at(sym.st_name).unwrap_or(name);
+
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+
+    // The DWARF of relocatable objects needs relocations to be applied.
+    let dwarf = if args.raw || elf.header.e_type == ET_REL {
+        None
+    } else {
+        load_dwarf(elf, bytes)?
+    };
+    let var = match dwarf {
+        Some(ref dwarf) => find_variable(dwarf, &sym)?,
+        None => None,
+    };
+    let ctx = match (&dwarf, &var) {
+        (Some(dwarf), Some((unit, _))) => Some(TypeCtx::new(elf, bytes, dwarf, unit)),
+        _ => None,
+    };
+    let ty = var.as_ref().and_then(|(_, ty)| *ty);
+
+    let size = match sym.st_size {
+        0 => ctx.as_ref()
+            .and_then(|ctx| ctx.type_size(ty, 0))
+            .unwrap_or(0),
+        size => size,
+    };
+    if size == 0 {
+        bail!("symbol {name:?} has no size");
+    }
+
+    let content = object_bytes(elf, bytes, &sym, size)?;
+    let section = elf.section_headers.get(sym.st_shndx)
+        .and_then(|sh| elf.shdr_strtab.get_at(sh.sh_name));
+
+    print_header(&format!("OBJECT {name:?}"));
+    let table = PairTable(18);
+
+    table.field("Name");
+    println!("{}", demangle(raw_name));
+
+    table.field("Address");
+    println!("{}", sp.hex(sym.st_value));
+
+    table.field("Size");
+    println!("{} B ({})", sp.hex(size), BinSize(size));
+
+    table.field("Section");
+    match section {
+        Some(section) => println!("{section}"),
+        None if sym.st_shndx == SHN_COMMON as usize => println!("\x1b[90mCOMMON\x1b[0m"),
+        None => println!("\x1b[90m-\x1b[0m"),
+    }
+
+    table.field("Type");
+    match ctx {
+        Some(ref ctx) => println!("{}", ctx.type_name(ty, 0)),
+        None if args.raw => println!("\x1b[90m-\x1b[0m"),
+        None => println!("\x1b[90mno DWARF information\x1b[0m"),
+    }
+
+    println!();
+    match content {
+        Content::File { ref data, offset } => {
+            let vaddr = (elf.header.e_type != ET_REL).then_some(sym.st_value);
+            hexdump_region(elf, vaddr, offset, data, false, 1);
+        },
+        Content::Zeroed => {
+            println!("\x1b[90m(zero-initialized, not stored in the file)\x1b[0m");
+        },
+    }
+
+    if let Some(ctx) = ctx {
+        let zeroed;
+        let data = match content {
+            Content::File { ref data, .. } => data,
+            Content::Zeroed => {
+                zeroed = vec![0; size.min(MAX_ZEROED) as usize];
+                &zeroed
+            },
+        };
+        let mut out = String::new();
+        ctx.value(ty, data, sym.st_value, 0, 0, &mut out)?;
+
+        println!();
+        print_header("VALUE");
+        println!("{} = {out}", demangle(raw_name));
+    }
+
+    Ok(())
+}
+
+enum Content {
+    File { data: Vec<u8>, offset: u64 },
+    /// NOBITS or common symbols.
+    Zeroed,
+}
+
+fn object_bytes(elf: &Elf, bytes: &[u8], sym: &Sym, size: u64) -> Result<Content> {
+    let sh = elf.section_headers.get(sym.st_shndx);
+    if sym.st_shndx == SHN_COMMON as usize || sh.is_some_and(|sh| sh.sh_type == SHT_NOBITS) {
+        return Ok(Content::Zeroed);
+    }
+
+    let (offset, mut data) = if elf.header.e_type == ET_REL {
+        let sh = sh.ok_or_else(|| anyhow!("the symbol's section doesn't exist"))?;
+        let offset = sh.sh_offset.saturating_add(sym.st_value);
+        let end = sh.sh_offset.saturating_add(sh.sh_size).min(offset.saturating_add(size));
+        let data = bytes.get((offset as usize)..(end as usize)).unwrap_or(&[]);
+        (offset, data.to_vec())
+    } else {
+        let offset = vaddr_file_offset(elf, sym.st_value);
+        let data = content_at_vaddr(elf, bytes, sym.st_value).unwrap_or(&[]);
+        let data = &data[..data.len().min(size as usize)];
+        match offset {
+            Some(offset) => (offset, data.to_vec()),
+            None => return Ok(Content::Zeroed),
+        }
+    };
+
+    // The tail of a segment that isn't stored in the file is zero-filled.
+    let len = data.len().max(size.min(MAX_ZEROED) as usize);
+    data.resize(len, 0);
+
+    Ok(Content::File { data, offset })
+}
+
+fn load_dwarf<'a>(elf: &Elf, bytes: &'a [u8]) -> Result<Option<Dwarf<R<'a>>>> {
+    let Some(debug_info) = find_section(elf, ".debug_info") else {
+        return Ok(None);
+    };
+    if debug_info.sh_flags & SHF_COMPRESSED as u64 != 0 {
+        println!("\x1b[93mWarning: compressed DWARF sections aren't supported\x1b[0m");
+        return Ok(None);
+    }
+
+    let endian = if elf.little_endian {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let dwarf = Dwarf::load(|id| -> Result<R<'a>, gimli::Error> {
+        let content = find_section(elf, id.name())
+            .and_then(|sh| sh.file_range())
+            .and_then(|r| bytes.get(r))
+            .unwrap_or(&[]);
+        Ok(EndianSlice::new(content, endian))
+    })?;
+
+    Ok(Some(dwarf))
+}
+
+/// Find the variable DIE located at a symbol's address, and return the unit
+/// it's in and its type.
+fn find_variable<'a>(
+    dwarf: &Dwarf<R<'a>>,
+    sym: &Sym,
+) -> Result<Option<(Unit<R<'a>>, Option<UnitOffset>)>> {
+    let mut units = dwarf.units();
+
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let mut entries = unit.entries();
+        let mut found = None;
+
+        while let Some((_, die)) = entries.next_dfs()? {
+            if die.tag() != gimli::DW_TAG_variable {
+                continue;
+            }
+            let Some(location) = die.attr_value(gimli::DW_AT_location)?
+                .and_then(|v| v.exprloc_value()) else { continue };
+            let addr = match location.operations(unit.encoding()).next()? {
+                Some(Operation::Address { address }) => address,
+                Some(Operation::AddressIndex { index }) => dwarf.address(&unit, index)?,
+                _ => continue,
+            };
+            if addr == sym.st_value {
+                found = Some(die.offset());
+                break;
+            }
+        }
+
+        if let Some(offset) = found {
+            // Definitions out of their class or namespace only refer to
+            // their declaration, which holds the type.
+            let mut die = unit.entry(offset)?;
+            if die.attr_value(gimli::DW_AT_type)?.is_none() {
+                if let Some(AttributeValue::UnitRef(decl)) = die.attr_value(gimli::DW_AT_specification)? {
+                    die = unit.entry(decl)?;
+                }
+            }
+            let ty = type_ref(&die)?;
+            return Ok(Some((unit, ty)));
+        }
+    }
+
+    Ok(None)
+}
+
+fn type_ref(die: &DebuggingInformationEntry<R>) -> Result<Option<UnitOffset>> {
+    Ok(match die.attr_value(gimli::DW_AT_type)? {
+        Some(AttributeValue::UnitRef(offset)) => Some(offset),
+        _ => None,
+    })
+}
+
+/// The value of a pointer slot patched by a relocation.
+enum Slot {
+    Value(u64),
+    Sym { name: String, addend: i64 },
+}
+
+struct TypeCtx<'a, 'e> {
+    elf: &'a Elf<'e>,
+    bytes: &'a [u8],
+    dwarf: &'a Dwarf<R<'a>>,
+    unit: &'a Unit<R<'a>>,
+    sp: SizePrint,
+    relocs: HashMap<u64, Slot>,
+}
+
+impl<'a, 'e> TypeCtx<'a, 'e> {
+    fn new(
+        elf: &'a Elf<'e>,
+        bytes: &'a [u8],
+        dwarf: &'a Dwarf<R<'a>>,
+        unit: &'a Unit<R<'a>>,
+    ) -> Self {
+        Self {
+            elf,
+            bytes,
+            dwarf,
+            unit,
+            sp: SizePrint::new(elf.header.container().unwrap_or(Container::Big)),
+            relocs: Self::slots(elf),
+        }
+    }
+
+    /// The pointer slots patched by dynamic relocations, by address.
+    fn slots(elf: &Elf) -> HashMap<u64, Slot> {
+        let mut slots = HashMap::new();
+        let relocs = elf.dynrelas.iter()
+            .chain(elf.dynrels.iter())
+            .chain(elf.pltrelocs.iter());
+        for rel in relocs {
+            let slot = if rel.r_sym == 0 {
+                match rel.r_addend {
+                    Some(addend) => Slot::Value(addend as u64),
+                    None => continue,
+                }
+            } else {
+                let name = elf.dynsyms.get(rel.r_sym)
+                    .and_then(|sym| elf.dynstrtab.get_at(sym.st_name))
+                    .unwrap_or("???");
+                Slot::Sym {
+                    name: demangle(name).to_string(),
+                    addend: rel.r_addend.unwrap_or(0),
+                }
+            };
+            slots.insert(rel.r_offset, slot);
+        }
+
+        slots
+    }
+
+    fn entry(&self, offset: UnitOffset) -> Result<DebuggingInformationEntry<'a, 'a, R<'a>>> {
+        Ok(self.unit.entry(offset)?)
+    }
+
+    fn name(&self, die: &DebuggingInformationEntry<R>) -> Result<Option<String>> {
+        Ok(match die.attr_value(gimli::DW_AT_name)? {
+            Some(attr) => Some(
+                self.dwarf.attr_string(self.unit, attr)?.to_string_lossy().into_owned()
+            ),
+            None => None,
+        })
+    }
+
+    fn udata(&self, die: &DebuggingInformationEntry<R>, at: DwAt) -> Result<Option<u64>> {
+        Ok(die.attr_value(at)?.and_then(|v| v.udata_value()))
+    }
+
+    fn children(&self, offset: UnitOffset) -> Result<Vec<DebuggingInformationEntry<'a, 'a, R<'a>>>> {
+        let mut tree = self.unit.entries_tree(Some(offset))?;
+        let root = tree.root()?;
+        let mut children = root.children();
+        let mut dies = Vec::new();
+        while let Some(child) = children.next()? {
+            dies.push(child.entry().clone());
+        }
+        Ok(dies)
+    }
+
+    /// Skip typedefs and qualifiers.
+    fn strip(&self, mut ty: Option<UnitOffset>) -> Result<Option<DebuggingInformationEntry<'a, 'a, R<'a>>>> {
+        for _ in 0..MAX_DEPTH {
+            let Some(offset) = ty else { return Ok(None) };
+            let die = self.entry(offset)?;
+            match die.tag() {
+                gimli::DW_TAG_typedef | gimli::DW_TAG_const_type | gimli::DW_TAG_volatile_type
+                | gimli::DW_TAG_restrict_type | gimli::DW_TAG_atomic_type => ty = type_ref(&die)?,
+                _ => return Ok(Some(die)),
+            }
+        }
+        Ok(None)
+    }
+
+    fn ptr_size(&self) -> u64 {
+        self.unit.encoding().address_size as u64
+    }
+
+    /// The dimensions of an array type, `None` for flexible arrays.
+    fn array_dims(&self, offset: UnitOffset) -> Result<Vec<Option<u64>>> {
+        let mut dims = Vec::new();
+        for die in self.children(offset)? {
+            if die.tag() != gimli::DW_TAG_subrange_type {
+                continue;
+            }
+            let count = match self.udata(&die, gimli::DW_AT_count)? {
+                Some(count) => Some(count),
+                None => {
+                    let lower = self.udata(&die, gimli::DW_AT_lower_bound)?.unwrap_or(0);
+                    die.attr_value(gimli::DW_AT_upper_bound)?
+                        .and_then(|v| v.sdata_value())
+                        .and_then(|upper| upper.checked_add(1)?.checked_sub(lower as i64))
+                        .map(|count| count.max(0) as u64)
+                },
+            };
+            dims.push(count);
+        }
+        Ok(dims)
+    }
+
+    fn type_size(&self, ty: Option<UnitOffset>, depth: usize) -> Option<u64> {
+        let die = self.entry(ty?).ok()?;
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        if let Ok(Some(size)) = self.udata(&die, gimli::DW_AT_byte_size) {
+            return Some(size);
+        }
+
+        match die.tag() {
+            gimli::DW_TAG_typedef | gimli::DW_TAG_const_type | gimli::DW_TAG_volatile_type
+            | gimli::DW_TAG_restrict_type | gimli::DW_TAG_atomic_type | gimli::DW_TAG_enumeration_type =>
+                self.type_size(type_ref(&die).ok()?, depth + 1),
+            gimli::DW_TAG_pointer_type | gimli::DW_TAG_reference_type | gimli::DW_TAG_rvalue_reference_type =>
+                Some(self.ptr_size()),
+            gimli::DW_TAG_array_type => {
+                let elem = self.type_size(type_ref(&die).ok()?, depth + 1)?;
+                self.array_dims(die.offset()).ok()?
+                    .into_iter()
+                    .try_fold(elem, |size, dim| size.checked_mul(dim?))
+            },
+            _ => None,
+        }
+    }
+
+    /// The type's name in C syntax.
+    fn type_name(&self, ty: Option<UnitOffset>, depth: usize) -> String {
+        let Some(die) = ty.and_then(|ty| self.entry(ty).ok()) else {
+            return "void".to_string();
+        };
+        if depth > MAX_DEPTH {
+            return "...".to_string();
+        }
+        let name = self.name(&die).ok().flatten();
+        let inner = || self.type_name(type_ref(&die).ok().flatten(), depth + 1);
+
+        match die.tag() {
+            gimli::DW_TAG_base_type | gimli::DW_TAG_typedef => name.unwrap_or_else(|| "?".to_string()),
+            gimli::DW_TAG_const_type => {
+                let target = type_ref(&die).ok().flatten()
+                    .and_then(|ty| self.entry(ty).ok())
+                    .map(|die| die.tag());
+                match target {
+                    // The qualifier of an array applies to its elements,
+                    // which are also qualified.
+                    Some(gimli::DW_TAG_array_type) => inner(),
+                    Some(gimli::DW_TAG_pointer_type) => format!("{} const", inner()),
+                    _ => format!("const {}", inner()),
+                }
+            },
+            gimli::DW_TAG_volatile_type => format!("volatile {}", inner()),
+            gimli::DW_TAG_restrict_type => format!("{} restrict", inner()),
+            gimli::DW_TAG_atomic_type => format!("_Atomic {}", inner()),
+            gimli::DW_TAG_pointer_type => format!("{} *", inner()),
+            gimli::DW_TAG_reference_type => format!("{} &", inner()),
+            gimli::DW_TAG_rvalue_reference_type => format!("{} &&", inner()),
+            gimli::DW_TAG_subroutine_type => "fn(...)".to_string(),
+            gimli::DW_TAG_array_type => {
+                let dims: String = self.array_dims(die.offset())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|dim| match dim {
+                        Some(n) => format!("[{n}]"),
+                        None => "[]".to_string(),
+                    })
+                    .collect();
+                format!("{}{dims}", inner())
+            },
+            tag => {
+                let kind = match tag {
+                    gimli::DW_TAG_structure_type => "struct ",
+                    gimli::DW_TAG_class_type => "class ",
+                    gimli::DW_TAG_union_type => "union ",
+                    gimli::DW_TAG_enumeration_type => "enum ",
+                    _ => "",
+                };
+                format!("{kind}{}", name.as_deref().unwrap_or("<anonymous>"))
+            },
+        }
+    }
+
+    /// Decode `data`, located at `addr`, as type `ty` and append it to `out`.
+    fn value(
+        &self,
+        ty: Option<UnitOffset>,
+        data: &[u8],
+        addr: u64,
+        indent: usize,
+        depth: usize,
+        out: &mut String,
+    ) -> Result<()> {
+        let Some(die) = self.strip(ty)? else {
+            out.push_str(&hex_bytes(data));
+            return Ok(());
+        };
+        if depth > MAX_DEPTH {
+            out.push_str("...");
+            return Ok(());
+        }
+        let size = self.type_size(Some(die.offset()), 0);
+        if size.is_some_and(|size| size as usize > data.len()) {
+            out.push_str("\x1b[90m<unavailable>\x1b[0m");
+            return Ok(());
+        }
+        let data = &data[..size.map_or(data.len(), |s| s as usize)];
+
+        match die.tag() {
+            gimli::DW_TAG_base_type => out.push_str(&self.base_value(&die, data)?),
+            gimli::DW_TAG_enumeration_type => out.push_str(&self.enum_value(&die, data)?),
+            gimli::DW_TAG_pointer_type | gimli::DW_TAG_reference_type | gimli::DW_TAG_rvalue_reference_type => {
+                out.push_str(&self.pointer_value(&die, data, addr)?);
+            },
+            gimli::DW_TAG_structure_type | gimli::DW_TAG_class_type | gimli::DW_TAG_union_type => {
+                self.struct_value(&die, data, addr, indent, depth, out)?;
+            },
+            gimli::DW_TAG_array_type => {
+                let elem = type_ref(&die)?;
+                let dims = self.array_dims(die.offset())?;
+                self.array_value(elem, &dims, data, addr, indent, depth, out)?;
+            },
+            _ => out.push_str(&hex_bytes(data)),
+        }
+
+        Ok(())
+    }
+
+    fn base_value(&self, die: &DebuggingInformationEntry<R>, data: &[u8]) -> Result<String> {
+        let encoding = match die.attr_value(gimli::DW_AT_encoding)? {
+            Some(AttributeValue::Encoding(encoding)) => encoding,
+            _ => return Ok(hex_bytes(data)),
+        };
+        if data.is_empty() || data.len() > 8 {
+            return Ok(hex_bytes(data));
+        }
+        let unsigned = self.read_uint(data);
+        let signed = self.read_int(data);
+
+        Ok(match encoding {
+            gimli::DW_ATE_boolean => match unsigned {
+                0 => "false".to_string(),
+                1 => "true".to_string(),
+                n => format!("{n} (true)"),
+            },
+            gimli::DW_ATE_float if data.len() == 4 => format!("{}", f32::from_bits(unsigned as u32)),
+            gimli::DW_ATE_float if data.len() == 8 => format!("{}", f64::from_bits(unsigned)),
+            gimli::DW_ATE_signed_char => char_value(signed, unsigned),
+            gimli::DW_ATE_unsigned_char | gimli::DW_ATE_UTF => char_value(unsigned as i64, unsigned),
+            gimli::DW_ATE_signed => signed.to_string(),
+            gimli::DW_ATE_unsigned if unsigned > 9 => format!("{unsigned} ({unsigned:#x})"),
+            gimli::DW_ATE_unsigned => unsigned.to_string(),
+            _ => hex_bytes(data),
+        })
+    }
+
+    fn enum_value(&self, die: &DebuggingInformationEntry<R>, data: &[u8]) -> Result<String> {
+        if data.is_empty() || data.len() > 8 {
+            return Ok(hex_bytes(data));
+        }
+        let mask = u64::MAX >> (64 - data.len() * 8);
+        let value = self.read_uint(data);
+        let signed = match self.strip(type_ref(die)?)? {
+            Some(base) => matches!(
+                base.attr_value(gimli::DW_AT_encoding)?,
+                Some(AttributeValue::Encoding(gimli::DW_ATE_signed | gimli::DW_ATE_signed_char)),
+            ),
+            None => false,
+        };
+        let number = if signed {
+            self.read_int(data).to_string()
+        } else {
+            value.to_string()
+        };
+
+        for child in self.children(die.offset())? {
+            if child.tag() != gimli::DW_TAG_enumerator {
+                continue;
+            }
+            let Some(attr) = child.attr_value(gimli::DW_AT_const_value)? else { continue };
+            let Some(enumerator) = attr.sdata_value().map(|v| v as u64)
+                .or_else(|| attr.udata_value()) else { continue };
+            if enumerator & mask == value {
+                let name = self.name(&child)?.unwrap_or_default();
+                return Ok(format!("{name} ({number})"));
+            }
+        }
+
+        Ok(number)
+    }
+
+    fn pointer_value(
+        &self,
+        die: &DebuggingInformationEntry<R>,
+        data: &[u8],
+        addr: u64,
+    ) -> Result<String> {
+        let pointee = self.strip(type_ref(die)?)?;
+        let is_char = match pointee {
+            Some(ref pointee) => pointee.tag() == gimli::DW_TAG_base_type
+                && self.udata(pointee, gimli::DW_AT_byte_size)? == Some(1)
+                && matches!(
+                    pointee.attr_value(gimli::DW_AT_encoding)?,
+                    Some(AttributeValue::Encoding(gimli::DW_ATE_signed_char | gimli::DW_ATE_unsigned_char)),
+                ),
+            None => false,
+        };
+
+        let value = match self.relocs.get(&addr) {
+            Some(Slot::Value(value)) => *value,
+            Some(Slot::Sym { name, addend }) => {
+                return Ok(match addend {
+                    0 => format!("&{name}"),
+                    addend => format!("&{name} + {addend:#x}"),
+                });
+            },
+            None => self.read_uint(data),
+        };
+        if value == 0 {
+            return Ok("NULL".to_string());
+        }
+
+        let mut s = self.sp.hex(value).to_string();
+        if is_char {
+            if let Some(content) = content_at_vaddr(self.elf, self.bytes, value) {
+                write!(s, " {:?}", c_string(content))?;
+                return Ok(s);
+            }
+        }
+        if let Some(label) = self.symbol_at(value) {
+            write!(s, " <{label}>")?;
+        }
+
+        Ok(s)
+    }
+
+    fn struct_value(
+        &self,
+        die: &DebuggingInformationEntry<R>,
+        data: &[u8],
+        addr: u64,
+        indent: usize,
+        depth: usize,
+        out: &mut String,
+    ) -> Result<()> {
+        let members: Vec<_> = self.children(die.offset())?
+            .into_iter()
+            .filter(|m| matches!(m.tag(), gimli::DW_TAG_member | gimli::DW_TAG_inheritance))
+            .filter(|m| !matches!(m.attr_value(gimli::DW_AT_declaration), Ok(Some(_))))
+            .collect();
+        if members.is_empty() {
+            out.push_str("{}");
+            return Ok(());
+        }
+
+        out.push_str("{\n");
+        for member in members {
+            let ty = type_ref(&member)?;
+            let name = match member.tag() {
+                gimli::DW_TAG_inheritance => format!("<{}>", self.type_name(ty, 0)),
+                _ => self.name(&member)?.unwrap_or_else(|| "<anonymous>".to_string()),
+            };
+            write!(out, "{:w$}.{name} = ", "", w = indent + 4)?;
+
+            let offset = self.member_offset(&member)?;
+            match self.udata(&member, gimli::DW_AT_bit_size)? {
+                Some(bit_size) => match self.bit_offset(&member, offset, bit_size)? {
+                    Some(bit_offset) => {
+                        out.push_str(&self.bitfield_value(ty, data, bit_offset, bit_size)?);
+                    },
+                    None => out.push_str("\x1b[90m<unavailable>\x1b[0m"),
+                },
+                None => match data.get((offset as usize)..) {
+                    Some(field) => {
+                        self.value(ty, field, addr.wrapping_add(offset), indent + 4, depth + 1, out)?
+                    },
+                    None => out.push_str("\x1b[90m<unavailable>\x1b[0m"),
+                },
+            }
+            out.push_str(",\n");
+        }
+        write!(out, "{:indent$}}}", "")?;
+
+        Ok(())
+    }
+
+    fn member_offset(&self, member: &DebuggingInformationEntry<R>) -> Result<u64> {
+        let Some(attr) = member.attr_value(gimli::DW_AT_data_member_location)? else {
+            return Ok(0);
+        };
+        if let Some(offset) = attr.udata_value() {
+            return Ok(offset);
+        }
+        if let Some(expr) = attr.exprloc_value() {
+            if let Some(Operation::PlusConstant { value }) = expr.operations(self.unit.encoding()).next()? {
+                return Ok(value);
+            }
+        }
+        Ok(0)
+    }
+
+    /// The offset of a bit-field in bits from the start of the structure,
+    /// using the machine's bit numbering, if it can be represented.
+    fn bit_offset(
+        &self,
+        member: &DebuggingInformationEntry<R>,
+        offset: u64,
+        bit_size: u64,
+    ) -> Result<Option<u64>> {
+        if let Some(bit_offset) = self.udata(member, gimli::DW_AT_data_bit_offset)? {
+            return Ok(Some(bit_offset));
+        }
+
+        // DWARF 2 and 3 count bits from the most significant bit of the
+        // storage unit.
+        let bit_offset = self.udata(member, gimli::DW_AT_bit_offset)?.unwrap_or(0);
+        let storage = self.udata(member, gimli::DW_AT_byte_size)?
+            .or_else(|| self.type_size(type_ref(member).ok().flatten(), 0))
+            .unwrap_or(0);
+        Ok(if self.elf.little_endian {
+            offset.checked_add(storage)
+                .and_then(|end| end.checked_mul(8))
+                .and_then(|end| end.checked_sub(bit_offset))
+                .and_then(|end| end.checked_sub(bit_size))
+        } else {
+            offset.checked_mul(8).and_then(|start| start.checked_add(bit_offset))
+        })
+    }
+
+    fn bitfield_value(
+        &self,
+        ty: Option<UnitOffset>,
+        data: &[u8],
+        bit_offset: u64,
+        bit_size: u64,
+    ) -> Result<String> {
+        if !(1..=64).contains(&bit_size) {
+            return Ok("\x1b[90m<unavailable>\x1b[0m".to_string());
+        }
+        let start = (bit_offset / 8) as usize;
+        let shift = bit_offset % 8;
+        let len = (shift + bit_size).div_ceil(8) as usize;
+        let bytes = start.checked_add(len).and_then(|end| data.get(start..end));
+        let Some(bytes) = bytes.filter(|_| len <= 8) else {
+            return Ok("\x1b[90m<unavailable>\x1b[0m".to_string());
+        };
+
+        let raw = self.read_uint(bytes);
+        let raw = if self.elf.little_endian {
+            raw >> shift
+        } else {
+            let Some(low) = (len as u64 * 8).checked_sub(shift + bit_size) else {
+                return Ok("\x1b[90m<unavailable>\x1b[0m".to_string());
+            };
+            raw >> low
+        };
+        let value = raw & (u64::MAX >> (64 - bit_size));
+
+        let signed = match self.strip(ty)? {
+            Some(base) => matches!(
+                base.attr_value(gimli::DW_AT_encoding)?,
+                Some(AttributeValue::Encoding(gimli::DW_ATE_signed | gimli::DW_ATE_signed_char)),
+            ),
+            None => false,
+        };
+        Ok(if signed {
+            let shift = 64 - bit_size;
+            (((value << shift) as i64) >> shift).to_string()
+        } else {
+            value.to_string()
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn array_value(
+        &self,
+        elem: Option<UnitOffset>,
+        dims: &[Option<u64>],
+        data: &[u8],
+        addr: u64,
+        indent: usize,
+        depth: usize,
+        out: &mut String,
+    ) -> Result<()> {
+        let inner_size = self.type_size(elem, 0).and_then(|size| {
+            dims.iter().skip(1).try_fold(size, |size, dim| size.checked_mul((*dim)?))
+        });
+        let Some(stride) = inner_size.filter(|&s| s > 0) else {
+            out.push_str(&hex_bytes(data));
+            return Ok(());
+        };
+        let count = dims.first()
+            .copied()
+            .flatten()
+            .unwrap_or(data.len() as u64 / stride) as usize;
+        let elem_die = self.strip(elem)?;
+
+        let is_char = dims.len() == 1 && stride == 1 && elem_die.as_ref().is_some_and(|die| {
+            matches!(
+                die.attr_value(gimli::DW_AT_encoding),
+                Ok(Some(AttributeValue::Encoding(gimli::DW_ATE_signed_char | gimli::DW_ATE_unsigned_char))),
+            )
+        });
+        if is_char {
+            write!(out, "{:?}", c_string(&data[..count.min(data.len())]))?;
+            return Ok(());
+        }
+
+        let is_scalar = dims.len() == 1 && elem_die.as_ref().is_some_and(|die| matches!(
+            die.tag(),
+            gimli::DW_TAG_base_type | gimli::DW_TAG_enumeration_type | gimli::DW_TAG_pointer_type,
+        ));
+        let shown = count.min(MAX_ELEMS);
+
+        let elem_value = |i: usize, indent: usize, out: &mut String| -> Result<()> {
+            let start = i.saturating_mul(stride as usize);
+            let elem_data = &data[start.min(data.len())..];
+            if dims.len() > 1 {
+                let addr = addr.wrapping_add(start as u64);
+                self.array_value(elem, &dims[1..], elem_data, addr, indent, depth + 1, out)
+            } else {
+                self.value(elem, elem_data, addr.wrapping_add(start as u64), indent, depth + 1, out)
+            }
+        };
+
+        if is_scalar && count <= MAX_INLINE {
+            out.push('[');
+            for i in 0..count {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                elem_value(i, indent, out)?;
+            }
+            out.push(']');
+            return Ok(());
+        }
+
+        out.push_str("[\n");
+        for i in 0..shown {
+            write!(out, "{:w$}[{i}] = ", "", w = indent + 4)?;
+            elem_value(i, indent + 4, out)?;
+            out.push_str(",\n");
+        }
+        if count > shown {
+            writeln!(out, "{:w$}\x1b[90m... {} more\x1b[0m", "", count - shown, w = indent + 4)?;
+        }
+        write!(out, "{:indent$}]", "")?;
+
+        Ok(())
+    }
+
+    /// The symbol containing `addr`, as `name` or `name + offset`.
+    fn symbol_at(&self, addr: u64) -> Option<String> {
+        let tabs = [(&self.elf.syms, &self.elf.strtab), (&self.elf.dynsyms, &self.elf.dynstrtab)];
+        tabs.into_iter()
+            .flat_map(|(tab, strtab)| tab.iter().map(move |sym| (sym, strtab)))
+            .filter(|(sym, _)| sym.st_shndx != 0 && sym.st_type() != STT_SECTION)
+            .filter(|(sym, _)| {
+                sym.st_value == addr
+                    || (sym.st_value..(sym.st_value + sym.st_size)).contains(&addr)
+            })
+            .find_map(|(sym, strtab)| {
+                let name = strtab.get_at(sym.st_name).filter(|n| !n.is_empty())?;
+                Some(match addr - sym.st_value {
+                    0 => demangle(name).to_string(),
+                    off => format!("{} + {off:#x}", demangle(name)),
+                })
+            })
+    }
+
+    fn read_uint(&self, data: &[u8]) -> u64 {
+        if self.elf.little_endian {
+            LittleEndian::read_uint(data, data.len())
+        } else {
+            BigEndian::read_uint(data, data.len())
+        }
+    }
+
+    fn read_int(&self, data: &[u8]) -> i64 {
+        if self.elf.little_endian {
+            LittleEndian::read_int(data, data.len())
+        } else {
+            BigEndian::read_int(data, data.len())
+        }
+    }
+}
+
+fn char_value(value: i64, unsigned: u64) -> String {
+    match char::from_u32(unsigned as u32) {
+        Some(c) if unsigned < 0x80 && !c.is_control() => format!("{value} {c:?}"),
+        _ => value.to_string(),
+    }
+}
+
+/// A NUL-terminated string, truncated to 256 bytes.
+fn c_string(data: &[u8]) -> String {
+    let len = data.iter()
+        .take(256)
+        .position(|&b| b == 0)
+        .unwrap_or(data.len().min(256));
+    String::from_utf8_lossy(&data[..len]).into_owned()
+}
+
+fn hex_bytes(data: &[u8]) -> String {
+    let hex: Vec<String> = data.iter().map(|b| format!("{b:02x}")).collect();
+    format!("<{}>", hex.join(" "))
+}
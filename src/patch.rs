@@ -0,0 +1,277 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Patching of bytes or instructions into a copy of the ELF.
+
+use std::ops::Range;
+use std::path::Path;
+use anyhow::{anyhow, bail, Context, Result};
+use goblin::container::Container;
+use goblin::elf::{Elf, SectionHeader};
+use goblin::elf::header::{EM_386, EM_X86_64, ET_REL};
+use goblin::elf::section_header::SHT_NOBITS;
+use iced_x86::{Decoder, DecoderOptions};
+
+use crate::args::{PatchArgs, Syntax};
+use crate::asm::assemble;
+use crate::elf::{find_symbol, ph_by_vaddr, vaddr_file_offset};
+use crate::func::{ColorOutput, new_formatter, print_instr_bytes, resolver_symbols};
+use crate::grep::parse_byte_pattern;
+use crate::print::{HexdumpOptions, HexMark, hexdump_with, print_header, SizePrint};
+use crate::sections::find_section;
+
+/// Where to write the patch.
+struct Target<'a> {
+    offset: u64,
+    vaddr: Option<u64>,
+    section: Option<&'a SectionHeader>,
+    /// The symbol's name and end address, when patching a symbol.
+    symbol: Option<(String, u64)>,
+}
+
+pub fn patch(elf: &Elf, bytes: &[u8], elf_path: &Path, args: &PatchArgs) -> Result<()> {
+    let target = locate(elf, args)?;
+    if target.section.is_some_and(|sh| sh.sh_type == SHT_NOBITS) {
+        bail!("the target is in a NOBITS section, which has no content in the file");
+    }
+
+    let content: Vec<Option<u8>> = match (&args.bytes, &args.asm) {
+        (Some(hex), _) => parse_byte_pattern(hex)?,
+        (None, Some(asm)) => {
+            let bitness = match elf.header.e_machine {
+                EM_X86_64 => 64,
+                EM_386 => 32,
+                _ => bail!("instructions can only be assembled for x86 and x86-64"),
+            };
+            let ip = target.vaddr
+                .ok_or_else(|| anyhow!("instructions can only be assembled at a virtual address"))?;
+            let resolve = |name: &str| {
+                find_symbol(&elf.syms, &elf.strtab, name)
+                    .or_else(|| find_symbol(&elf.dynsyms, &elf.dynstrtab, name))
+                    .filter(|sym| sym.st_shndx != 0)
+                    .map(|sym| sym.st_value)
+            };
+            assemble(asm, bitness, ip, &resolve)?
+                .into_iter()
+                .map(Some)
+                .collect()
+        },
+        (None, None) => bail!("specify the bytes to write with `--bytes` or `--asm`"),
+    };
+
+    let end = target.offset.checked_add(content.len() as u64)
+        .ok_or_else(|| anyhow!("file offset {:#x} out of range", target.offset))?;
+    let range = target.offset..end;
+    let bounds = file_bounds(elf, &target)?;
+    if range.start < bounds.start || range.end > bounds.end {
+        bail!(
+            "writing {} byte(s) at file offset {:#x} would cross the boundary of the {} \
+             ({:#x}..{:#x})",
+            content.len(), range.start,
+            if target.section.is_some() { "section" } else { "segment" },
+            bounds.start, bounds.end,
+        );
+    }
+    if range.end > bytes.len() as u64 {
+        bail!("the patch goes past the end of the file");
+    }
+    if let Some((ref name, end)) = target.symbol {
+        let vend = target.vaddr.map(|v| v.saturating_add(content.len() as u64));
+        if vend.is_some_and(|vend| vend > end) {
+            println!("\x1b[93mWarning: the patch extends past the end of {name:?}\x1b[0m");
+        }
+    }
+
+    let same_file = match (std::fs::canonicalize(elf_path), std::fs::canonicalize(&args.output)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    };
+    if same_file {
+        bail!("refusing to overwrite the input ELF, write the patched copy to another file");
+    }
+
+    let mut patched = bytes.to_vec();
+    for (i, byte) in content.iter().enumerate() {
+        if let Some(byte) = byte {
+            patched[range.start as usize + i] = *byte;
+        }
+    }
+    std::fs::write(&args.output, &patched).with_context(||
+        format!("couldn't write the patched ELF to '{}'", args.output.display())
+    )?;
+
+    let window = (range.start & !15).max(bounds.start)
+        ..((range.end + 15) & !15).min(bounds.end);
+    print_header("BEFORE");
+    print_window(elf, &target, bytes, &window, &range, "1;91");
+    println!();
+    print_header("AFTER");
+    print_window(elf, &target, &patched, &window, &range, "1;92");
+
+    if args.asm.is_some() {
+        let ip = target.vaddr.unwrap_or(0);
+        let new = &patched[(range.start as usize)..(range.end as usize)];
+        print_disassembly(elf, bytes, new, ip);
+    }
+
+    println!();
+    println!(
+        "Patched {} byte(s) at file offset {:#x} into \"{}\"",
+        content.len(), range.start, args.output.display(),
+    );
+
+    Ok(())
+}
+
+fn locate<'a>(elf: &'a Elf, args: &PatchArgs) -> Result<Target<'a>> {
+    if let Some(vaddr) = args.addr {
+        return at_vaddr(elf, vaddr, None);
+    }
+
+    let out_of_range = |spec: &str| anyhow!("{spec:?}: offset out of range");
+
+    if let Some(ref spec) = args.sym {
+        let (name, off) = split_offset(spec)?;
+        let sym = find_symbol(&elf.syms, &elf.strtab, name)
+            .or_else(|| find_symbol(&elf.dynsyms, &elf.dynstrtab, name))
+            .filter(|sym| sym.st_shndx != 0)
+            .ok_or_else(|| anyhow!("couldn't find any symbol matching {name:?}"))?;
+
+        if elf.header.e_type == ET_REL {
+            let sh = elf.section_headers.get(sym.st_shndx)
+                .ok_or_else(|| anyhow!("the section of {name:?} doesn't exist"))?;
+            return Ok(Target {
+                offset: sh.sh_offset.checked_add(sym.st_value)
+                    .and_then(|offset| offset.checked_add(off))
+                    .ok_or_else(|| out_of_range(spec))?,
+                vaddr: None,
+                section: Some(sh),
+                symbol: None,
+            });
+        }
+
+        let symbol = (sym.st_size > 0)
+            .then(|| (name.to_string(), sym.st_value.saturating_add(sym.st_size)));
+        let vaddr = sym.st_value.checked_add(off).ok_or_else(|| out_of_range(spec))?;
+        return at_vaddr(elf, vaddr, symbol);
+    }
+
+    if let Some(ref spec) = args.section {
+        let (name, off) = split_offset(spec)?;
+        let sh = find_section(elf, name)
+            .ok_or_else(|| anyhow!("couldn't find section {name:?}"))?;
+        let vaddr = sh.is_alloc()
+            .then(|| sh.sh_addr.checked_add(off).ok_or_else(|| out_of_range(spec)))
+            .transpose()?;
+        return Ok(Target {
+            offset: sh.sh_offset.checked_add(off).ok_or_else(|| out_of_range(spec))?,
+            vaddr,
+            section: Some(sh),
+            symbol: None,
+        });
+    }
+
+    bail!("specify where to patch with `--addr`, `--sym`, or `--section`");
+}
+
+/// Map a virtual address to the file the same way as symbols are, with the
+/// program headers.
+fn at_vaddr<'a>(
+    elf: &'a Elf,
+    vaddr: u64,
+    symbol: Option<(String, u64)>,
+) -> Result<Target<'a>> {
+    let section = elf.section_headers.iter()
+        .filter(|sh| sh.is_alloc())
+        .find(|sh| vaddr.checked_sub(sh.sh_addr).is_some_and(|off| off < sh.sh_size));
+    if section.is_some_and(|sh| sh.sh_type == SHT_NOBITS) {
+        bail!("{vaddr:#x} is in a NOBITS section, which has no content in the file");
+    }
+    let offset = vaddr_file_offset(elf, vaddr)
+        .ok_or_else(|| anyhow!("{vaddr:#x} isn't backed by the file's content"))?;
+
+    Ok(Target { offset, vaddr: Some(vaddr), section, symbol })
+}
+
+/// The file range the patch must stay within: its section's, or its
+/// segment's when the ELF has no section headers.
+fn file_bounds(elf: &Elf, target: &Target) -> Result<Range<u64>> {
+    if let Some(sh) = target.section {
+        return Ok(sh.sh_offset..sh.sh_offset.saturating_add(sh.sh_size));
+    }
+    if !elf.section_headers.is_empty() {
+        bail!("file offset {:#x} isn't in any section", target.offset);
+    }
+
+    let ph = target.vaddr
+        .and_then(|vaddr| ph_by_vaddr(elf, vaddr))
+        .ok_or_else(|| anyhow!("file offset {:#x} isn't in any segment", target.offset))?;
+    Ok(ph.p_offset..ph.p_offset.saturating_add(ph.p_filesz))
+}
+
+/// Split `NAME+OFFSET`, the offset being decimal or hexadecimal with `0x`.
+fn split_offset(spec: &str) -> Result<(&str, u64)> {
+    let Some((name, off)) = spec.rsplit_once('+') else {
+        return Ok((spec, 0));
+    };
+    let off = match off.trim().strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => off.trim().parse(),
+    };
+
+    match off {
+        Ok(off) => Ok((name.trim(), off)),
+        // E.g. C++'s `operator+`.
+        Err(_) => Ok((spec, 0)),
+    }
+}
+
+fn print_window(
+    elf: &Elf,
+    target: &Target,
+    content: &[u8],
+    window: &Range<u64>,
+    patch: &Range<u64>,
+    color: &'static str,
+) {
+    // Label rows with virtual addresses when the target has one.
+    let to_label = |off: u64| match target.vaddr {
+        Some(vaddr) => vaddr.wrapping_add(off).wrapping_sub(target.offset),
+        None => off,
+    };
+
+    hexdump_with(&content[(window.start as usize)..(window.end as usize)], &HexdumpOptions {
+        base: to_label(window.start),
+        sp: Some(SizePrint::new(elf.header.container().unwrap_or(Container::Big))),
+        word: 1,
+        little_endian: elf.little_endian,
+        marks: vec![HexMark {
+            range: to_label(patch.start)..to_label(patch.end),
+            color,
+            note: String::new(),
+        }],
+    });
+}
+
+fn print_disassembly(elf: &Elf, bytes: &[u8], code: &[u8], ip: u64) {
+    let bitness = if elf.is_64 { 64 } else { 32 };
+    let sp = SizePrint::new(elf.header.container().unwrap_or(Container::Big));
+    let mut formatter = new_formatter(resolver_symbols(elf, bytes), Syntax::Intel);
+
+    println!();
+    print_header("ASSEMBLED");
+    for instr in Decoder::with_ip(bitness, code, ip, DecoderOptions::NONE) {
+        let start = (instr.ip() - ip) as usize;
+        print!("{} \x1b[97m│\x1b[0m  ", sp.hex(instr.ip()));
+        print_instr_bytes(&code[start..(start + instr.len())]);
+        formatter.format(&instr, &mut ColorOutput);
+        println!("\x1b[0m");
+    }
+}
@@ -9,11 +9,12 @@
  ******************************************************************************/
 
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
 use goblin::container::Container;
 
 pub fn print_header(name: &str) {
     print!("\x1b[1;96m───┤ {name} ├");
-    println!("{:─<w$}\x1b[0m", "", w = 70 - name.len());
+    println!("{:─<w$}\x1b[0m", "", w = 70usize.saturating_sub(name.len()));
 }
 
 pub struct PairTable(pub usize);
@@ -94,43 +95,109 @@ impl Display for BinSize {
     }
 }
 
-pub fn hexdump(data: &[u8]) {
-    hexdump_off(data, 0);
+/// A range of bytes to highlight in a hexdump, with a note displayed at the
+/// end of the row where it starts.
+pub struct HexMark {
+    pub range: Range<u64>,
+    /// ANSI SGR parameters, e.g. `"36"` or `"4;33"`.
+    pub color: &'static str,
+    pub note: String,
 }
 
-pub fn hexdump_off(data: &[u8], off: usize) {
-    for start in (off..data.len()).into_iter().step_by(16) {
-        print!("\x1b[97m{start:8x} │\x1b[0m ");
+pub struct HexdumpOptions {
+    /// The address or offset labelling the first byte.
+    pub base: u64,
+    /// Format the labels as addresses, rather than short offsets.
+    pub sp: Option<SizePrint>,
+    /// Group bytes as words of this size, in the ELF's endianness.
+    pub word: usize,
+    pub little_endian: bool,
+    /// Highlighted ranges, in the same unit as `base`; later marks take
+    /// precedence over earlier ones.
+    pub marks: Vec<HexMark>,
+}
+
+/// Hexdump with rows aligned on the labels, optionally grouped by words and
+/// with highlighted ranges.
+pub fn hexdump_with(data: &[u8], opts: &HexdumpOptions) {
+    let end = opts.base + data.len() as u64;
+    let byte_at = |addr: u64| {
+        (opts.base..end).contains(&addr).then(|| data[(addr - opts.base) as usize])
+    };
+
+    let mut order: Vec<usize> = (0..opts.marks.len()).collect();
+    order.sort_by_key(|&i| opts.marks[i].range.start);
+    let mut pending = order.into_iter().peekable();
+    let mut active: Vec<usize> = Vec::new();
+
+    let mut row = opts.base & !15;
+    while row < end {
+        let row_end = row + 16;
+        active.retain(|&i| opts.marks[i].range.end > row);
+        let mut notes = Vec::new();
+        while let Some(&i) = pending.peek() {
+            let mark = &opts.marks[i];
+            if mark.range.start >= row_end {
+                break;
+            }
+            pending.next();
+            if mark.range.end > row {
+                active.push(i);
+            }
+            notes.push(i);
+        }
+        let color_at = |addr: u64| {
+            active.iter()
+                .filter(|&&i| opts.marks[i].range.contains(&addr))
+                .max()
+                .map(|&i| opts.marks[i].color)
+        };
+
+        match opts.sp {
+            Some(sp) => print!("\x1b[97m{} │\x1b[0m ", sp.hex(row)),
+            None => print!("\x1b[97m{row:8x} │\x1b[0m "),
+        }
 
-        for i in start..(start + 16) {
-            if i % 8 == 0 && i % 16 != 0 {
+        for group in (0..16).step_by(opts.word) {
+            if group == 8 {
                 print!(" \x1b[97m│\x1b[0m");
             }
-            if i < data.len() {
-                print!(" {:02x}", data[i]);
-            } else {
-                print!("   ");
+            print!(" ");
+            for k in 0..opts.word {
+                let i = if opts.little_endian { group + opts.word - 1 - k } else { group + k };
+                let addr = row + i as u64;
+                match (byte_at(addr), color_at(addr)) {
+                    (Some(b), Some(color)) => print!("\x1b[{color}m{b:02x}\x1b[0m"),
+                    (Some(b), None) => print!("{b:02x}"),
+                    (None, _) => print!("  "),
+                }
             }
         }
 
         print!("  \x1b[97m│\x1b[0m");
-
-        for i in start..(start + 16) {
-            if i % 8 == 0 && i % 16 != 0 {
+        for i in 0..16 {
+            if i == 8 {
                 print!("\x1b[97m│\x1b[0m");
             }
-            if i < data.len() {
-                let b = data[i];
-                if b >= b' ' && b <= b'~' {
-                    print!("{}", b as char);
-                } else {
-                    print!("\x1b[90m╳\x1b[0m");
-                }
-            } else {
-                print!("\x1b[90m─\x1b[0m");
+            let addr = row + i;
+            match byte_at(addr) {
+                Some(b) if (b' '..=b'~').contains(&b) => match color_at(addr) {
+                    Some(color) => print!("\x1b[{color}m{}\x1b[0m", b as char),
+                    None => print!("{}", b as char),
+                },
+                Some(_) => print!("\x1b[90m╳\x1b[0m"),
+                None => print!("\x1b[90m─\x1b[0m"),
             }
         }
+        print!("\x1b[97m│\x1b[0m");
+
+        for (n, i) in notes.into_iter().enumerate() {
+            let mark = &opts.marks[i];
+            let sep = if n == 0 { " " } else { ", " };
+            print!("{sep}\x1b[{}m{}\x1b[0m", mark.color, mark.note);
+        }
+        println!();
 
-        print!("\x1b[97m│\x1b[0m\n");
+        row = row_end;
     }
 }
@@ -0,0 +1,210 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Reconstruction of synthetic section headers, from the program headers and
+//! the dynamic section, for ELFs that have none.
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use goblin::elf::{Elf, SectionHeader};
+use goblin::elf::dynamic::*;
+use goblin::elf::program_header::{PT_DYNAMIC, PT_GNU_EH_FRAME, PT_INTERP, PT_NOTE};
+use goblin::elf::section_header::*;
+use goblin::strtab::Strtab;
+
+use crate::eh::parse_eh_frame_hdr;
+use crate::elf::{content_at_vaddr, ph_by_vaddr, vaddr_file_offset};
+use crate::hash::{gnu_hash_layout, sysv_hash_layout};
+
+/// Names of the synthetic sections, which don't exist in the file.
+const NAMES: &str = "\0.interp\0.note\0.gnu.hash\0.hash\0.dynsym\0.dynstr\0.rela.dyn\0\
+                     .rel.dyn\0.rela.plt\0.rel.plt\0.init_array\0.fini_array\0.dynamic\0\
+                     .eh_frame_hdr\0.eh_frame\0";
+
+struct Synthetic {
+    name: &'static str,
+    sh: SectionHeader,
+    link: Option<&'static str>,
+}
+
+/// Replace the (empty) section headers with ones reconstructed from the
+/// program headers, and return how many were recovered.
+pub fn recover_sections(elf: &mut Elf, bytes: &[u8]) -> usize {
+    let mut sections = Vec::new();
+    let mut add = |elf: &Elf, name, sh_type, vaddr: u64, size: u64, link| {
+        let Some(offset) = vaddr_file_offset(elf, vaddr) else {
+            return;
+        };
+        let ph = ph_by_vaddr(elf, vaddr);
+        let mut flags = SHF_ALLOC as u64;
+        if ph.is_some_and(|ph| ph.is_write()) {
+            flags |= SHF_WRITE as u64;
+        }
+        if ph.is_some_and(|ph| ph.is_executable()) {
+            flags |= SHF_EXECINSTR as u64;
+        }
+        let (entsize, align) = entry_size(elf, sh_type);
+
+        sections.push(Synthetic {
+            name,
+            sh: SectionHeader {
+                sh_type,
+                sh_flags: flags,
+                sh_addr: vaddr,
+                sh_offset: offset,
+                sh_size: size,
+                sh_addralign: align,
+                sh_entsize: entsize,
+                ..Default::default()
+            },
+            link,
+        });
+    };
+
+    for ph in &elf.program_headers {
+        match ph.p_type {
+            PT_INTERP => add(elf, ".interp", SHT_PROGBITS, ph.p_vaddr, ph.p_filesz, None),
+            PT_NOTE => add(elf, ".note", SHT_NOTE, ph.p_vaddr, ph.p_filesz, None),
+            PT_DYNAMIC => add(elf, ".dynamic", SHT_DYNAMIC, ph.p_vaddr, ph.p_filesz,
+                              Some(".dynstr")),
+            PT_GNU_EH_FRAME => {
+                add(elf, ".eh_frame_hdr", SHT_PROGBITS, ph.p_vaddr, ph.p_filesz, None);
+                if let Some((vaddr, size)) = eh_frame(elf, bytes, ph.p_vaddr) {
+                    add(elf, ".eh_frame", SHT_PROGBITS, vaddr, size, None);
+                }
+            },
+            _ => (),
+        }
+    }
+
+    if let Some(ref dynamic) = elf.dynamic {
+        let tag = |tag: u64| dynamic.dyns.iter()
+            .find(|d| d.d_tag == tag)
+            .map(|d| d.d_val);
+        let sym_size = entry_size(elf, SHT_DYNSYM).0;
+
+        if let (Some(addr), Some(size)) = (tag(DT_STRTAB), tag(DT_STRSZ)) {
+            add(elf, ".dynstr", SHT_STRTAB, addr, size, None);
+        }
+
+        let gnu_hash = tag(DT_GNU_HASH).and_then(|addr| {
+            let content = content_at_vaddr(elf, bytes, addr)?;
+            Some((addr, gnu_hash_layout(elf, content).ok()?))
+        });
+        let hash = tag(DT_HASH).and_then(|addr| {
+            let content = content_at_vaddr(elf, bytes, addr)?;
+            Some((addr, sysv_hash_layout(elf, content).ok()?))
+        });
+        if let Some((addr, (count, size))) = gnu_hash {
+            add(elf, ".gnu.hash", SHT_GNU_HASH, addr, size, Some(".dynsym"));
+            if let Some(symtab) = tag(DT_SYMTAB) {
+                add(elf, ".dynsym", SHT_DYNSYM, symtab, count * sym_size, Some(".dynstr"));
+            }
+        }
+        if let Some((addr, (nchain, size))) = hash {
+            add(elf, ".hash", SHT_HASH, addr, size, Some(".dynsym"));
+            if let (None, Some(symtab)) = (gnu_hash, tag(DT_SYMTAB)) {
+                add(elf, ".dynsym", SHT_DYNSYM, symtab, nchain * sym_size, Some(".dynstr"));
+            }
+        }
+
+        if let (Some(addr), Some(size)) = (tag(DT_RELA), tag(DT_RELASZ)) {
+            add(elf, ".rela.dyn", SHT_RELA, addr, size, Some(".dynsym"));
+        }
+        if let (Some(addr), Some(size)) = (tag(DT_REL), tag(DT_RELSZ)) {
+            add(elf, ".rel.dyn", SHT_REL, addr, size, Some(".dynsym"));
+        }
+        if let (Some(addr), Some(size)) = (tag(DT_JMPREL), tag(DT_PLTRELSZ)) {
+            if tag(DT_PLTREL) == Some(DT_RELA) {
+                add(elf, ".rela.plt", SHT_RELA, addr, size, Some(".dynsym"));
+            } else {
+                add(elf, ".rel.plt", SHT_REL, addr, size, Some(".dynsym"));
+            }
+        }
+        if let (Some(addr), Some(size)) = (tag(DT_INIT_ARRAY), tag(DT_INIT_ARRAYSZ)) {
+            add(elf, ".init_array", SHT_INIT_ARRAY, addr, size, None);
+        }
+        if let (Some(addr), Some(size)) = (tag(DT_FINI_ARRAY), tag(DT_FINI_ARRAYSZ)) {
+            add(elf, ".fini_array", SHT_FINI_ARRAY, addr, size, None);
+        }
+    }
+
+    sections.sort_by_key(|s| s.sh.sh_addr);
+
+    let strtab = Strtab::parse(NAMES.as_bytes(), 0, NAMES.len(), 0).unwrap_or_default();
+    let name_offset = |name: &str| NAMES.find(&format!("\0{name}\0")).unwrap_or(0) + 1;
+    let index_of = |name: &str| sections.iter().position(|s| s.name == name).map(|i| i + 1);
+
+    let mut headers = vec![SectionHeader::default()];
+    for s in &sections {
+        let mut sh = s.sh.clone();
+        sh.sh_name = name_offset(s.name);
+        sh.sh_link = s.link.and_then(index_of).unwrap_or(0) as u32;
+        if sh.sh_type == SHT_RELA || sh.sh_type == SHT_REL {
+            sh.sh_flags |= SHF_INFO_LINK as u64;
+        }
+        headers.push(sh);
+    }
+
+    let count = sections.len();
+    elf.section_headers = headers;
+    elf.shdr_strtab = strtab;
+    count
+}
+
+fn entry_size(elf: &Elf, sh_type: u32) -> (u64, u64) {
+    let word = if elf.is_64 { 8 } else { 4 };
+    match sh_type {
+        SHT_DYNSYM => (if elf.is_64 { 24 } else { 16 }, word),
+        SHT_RELA => (if elf.is_64 { 24 } else { 12 }, word),
+        SHT_REL => (if elf.is_64 { 16 } else { 8 }, word),
+        SHT_DYNAMIC => (word * 2, word),
+        SHT_INIT_ARRAY | SHT_FINI_ARRAY => (word, word),
+        SHT_HASH => (4, 4),
+        SHT_GNU_HASH => (0, word),
+        SHT_NOTE => (0, 4),
+        _ => (0, 1),
+    }
+}
+
+/// The address and size of `.eh_frame`, from the `.eh_frame_hdr` at `vaddr`:
+/// its CIEs and FDEs are walked up to the zero terminator.
+fn eh_frame(elf: &Elf, bytes: &[u8], hdr_vaddr: u64) -> Option<(u64, u64)> {
+    let hdr = content_at_vaddr(elf, bytes, hdr_vaddr)?;
+    let vaddr = parse_eh_frame_hdr(elf, bytes, hdr_vaddr, hdr).ok()?.eh_frame_ptr;
+    let content = content_at_vaddr(elf, bytes, vaddr)?;
+
+    let read = |off: usize, size: usize| -> Option<u64> {
+        let data = content.get(off..off.checked_add(size)?)?;
+        Some(if elf.little_endian {
+            LittleEndian::read_uint(data, size)
+        } else {
+            BigEndian::read_uint(data, size)
+        })
+    };
+    let mut off = 0;
+    loop {
+        let next = match read(off, 4) {
+            None => break,
+            Some(0) => {
+                off += 4;
+                break;
+            },
+            Some(0xffff_ffff) => usize::try_from(read(off + 4, 8)?).ok()
+                .and_then(|len| len.checked_add(12)),
+            Some(len) => (len as usize).checked_add(4),
+        };
+        match next.and_then(|len| off.checked_add(len)) {
+            Some(next) => off = next,
+            None => break,
+        }
+    }
+
+    Some((vaddr, off.min(content.len()) as u64))
+}
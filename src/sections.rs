@@ -12,15 +12,17 @@ use std::fs::File;
 use std::io::Write;
 use goblin::container::{Container};
 use goblin::elf::{Elf, SectionHeader};
-use goblin::elf::section_header::sht_to_str;
+use goblin::elf::section_header::{sht_to_str, SHT_GNU_HASH, SHT_HASH};
 use goblin::elf32::section_header::SHT_STRTAB;
 use goblin::strtab::Strtab;
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 
 use crate::{PairTable, print_header, SizePrint};
 use crate::args::SectionArgs;
-use crate::eh::{eh_frame, eh_frame_hdr};
-use crate::print::{BinSize, hexdump};
+use crate::eh::{debug_frame, eh_frame, eh_frame_hdr};
+use crate::dump::hexdump_region;
+use crate::hash::{gnu_hash_table, sysv_hash_table};
+use crate::print::BinSize;
 
 pub fn all_sections(elf: &Elf) {
     let container = elf.header.container().unwrap_or(Container::Big);
@@ -45,7 +47,7 @@ pub fn all_sections(elf: &Elf) {
 
     for (i, sh) in elf.section_headers.iter().enumerate() {
         let (type_c, type_n) = section_type(sh.sh_type);
-        let name = elf.shdr_strtab.get_at(sh.sh_name).unwrap(); // TODO
+        let name = elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("<invalid name>");
         print!("{i:2} \x1b[97m│\x1b[0m {name:20} \x1b[97m│\x1b[0m ");
         print!("\x1b[{type_c}{type_n:12} \x1b[97m│\x1b[0m ");
 
@@ -70,7 +72,8 @@ pub fn all_sections(elf: &Elf) {
 
 pub fn one_section(elf: &Elf, bytes: &[u8], opts: &SectionArgs) -> Result<()> {
     if opts.name.is_none() {
-        return Ok(all_sections(elf));
+        all_sections(elf);
+        return Ok(());
     }
 
     let name = opts.name.as_ref().unwrap();
@@ -79,6 +82,12 @@ pub fn one_section(elf: &Elf, bytes: &[u8], opts: &SectionArgs) -> Result<()> {
 
     let mut index_range = sh.file_range();
     if let Some(ref mut index_range) = index_range {
+        if index_range.end > bytes.len() {
+            println!("\x1b[93mWarning: the section goes past the end of the file, its content \
+                      is truncated\x1b[0m");
+            index_range.end = bytes.len();
+            index_range.start = index_range.start.min(index_range.end);
+        }
         if let Some(skip) = opts.skip {
             if skip >= index_range.len() {
                 println!("\x1b[93mWarning: skipping more bytes than in section\x1b[0m");
@@ -90,7 +99,7 @@ pub fn one_section(elf: &Elf, bytes: &[u8], opts: &SectionArgs) -> Result<()> {
                 index_range.end -= index_range.len() - maxlen;
             }
         }
-        if index_range.len() == 0 {
+        if index_range.start >= index_range.end {
             *index_range = 0..0;
         }
     } else {
@@ -119,6 +128,10 @@ pub fn one_section(elf: &Elf, bytes: &[u8], opts: &SectionArgs) -> Result<()> {
         return Ok(());
     }
 
+    if opts.lookup.is_some() && sh.sh_type != SHT_HASH && sh.sh_type != SHT_GNU_HASH {
+        bail!("`--lookup` only applies to `.hash` and `.gnu.hash` sections");
+    }
+
     print_header(&format!("SECTION {name:?}"));
 
     let table = PairTable(18);
@@ -155,19 +168,34 @@ pub fn one_section(elf: &Elf, bytes: &[u8], opts: &SectionArgs) -> Result<()> {
 
     if let Some(index_range) = index_range {
         println!();
+        let skip = (index_range.start as u64).saturating_sub(sh.sh_offset);
         let content = &bytes[index_range];
+        let hexdump = || hexdump_region(
+            elf,
+            sh.is_alloc().then_some(sh.sh_addr + skip),
+            sh.sh_offset + skip,
+            content,
+            opts.file_offsets,
+            opts.word,
+        );
 
         if opts.hexdump {
-            hexdump(content);
+            hexdump();
         } else {
             if sh.sh_type == SHT_STRTAB {
                 strtab(content)?;
             } else if name == ".eh_frame_hdr" {
-                eh_frame_hdr(elf, sh.sh_addr, content);
+                eh_frame_hdr(elf, bytes, sh.sh_addr, content)?;
             } else if name == ".eh_frame" {
-                eh_frame(elf, sh.sh_addr, content, &Default::default())?;
+                eh_frame(elf, bytes, sh.sh_addr, content, &Default::default())?;
+            } else if name == ".debug_frame" {
+                debug_frame(elf, bytes, content, &Default::default())?;
+            } else if sh.sh_type == SHT_HASH {
+                sysv_hash_table(elf, content, opts.lookup.as_deref())?;
+            } else if sh.sh_type == SHT_GNU_HASH {
+                gnu_hash_table(elf, content, opts.lookup.as_deref())?;
             } else {
-                hexdump(content);
+                hexdump();
             }
         }
     }
@@ -185,7 +213,7 @@ pub fn find_section<'a>(elf: &'a Elf, name: &str) -> Option<&'a SectionHeader> {
         })
 }
 
-fn section_type(typ: u32) -> (&'static str, &'static str) {
+pub fn section_type(typ: u32) -> (&'static str, &'static str) {
     match sht_to_str(typ) {
         "UNKNOWN_SHT" => ("93m", "[unknown]"),
         "SHT_PROGBITS" => ("34m", "PROGBITS"),
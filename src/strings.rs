@@ -0,0 +1,272 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Extraction of printable strings from sections or segments.
+
+use std::collections::HashSet;
+use anyhow::{anyhow, Result};
+use goblin::container::Container;
+use goblin::elf::Elf;
+use goblin::elf::program_header::PT_LOAD;
+use goblin::elf::section_header::{SHT_NOBITS, SHT_NULL};
+use iced_x86::OpKind;
+
+use crate::args::{StringEncoding, StringsArgs};
+use crate::print::{print_header, SizePrint};
+use crate::sections::find_section;
+use crate::xrefs::{decode_code, instr_xref, XrefKind};
+
+/// A region of the file to extract strings from.
+struct Region<'a> {
+    name: String,
+    vaddr: Option<u64>,
+    offset: u64,
+    content: &'a [u8],
+}
+
+struct Found {
+    start: usize,
+    encoding: StringEncoding,
+    text: String,
+}
+
+pub fn strings(elf: &Elf, bytes: &[u8], args: &StringsArgs) -> Result<()> {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+
+    let referenced: Option<HashSet<u64>> = if args.referenced {
+        let mut targets = HashSet::new();
+        decode_code(elf, bytes, |instr| {
+            if let Some((XrefKind::Data, target)) = instr_xref(&instr) {
+                targets.insert(target);
+            }
+            // Non-PIC code loads addresses as immediates, e.g. `mov edi, 0x402010`.
+            targets.extend((0..instr.op_count())
+                .filter(|&i| matches!(
+                    instr.op_kind(i),
+                    OpKind::Immediate32 | OpKind::Immediate64 | OpKind::Immediate32to64,
+                ))
+                .map(|i| instr.immediate(i)));
+        })?;
+        Some(targets)
+    } else {
+        None
+    };
+
+    let regions = regions(elf, bytes, args)?;
+
+    print_header("STRINGS");
+    let colw = match container {
+        Container::Big => 19,
+        Container::Little => 11,
+    };
+    println!(
+        "\x1b[97m{:20} │ {:>colw$} │ {:>10} │ {:6} │ String\x1b[0m",
+        if args.segments { "Segment" } else { "Section" },
+        "Address", "Offset", "Enc",
+    );
+    println!(
+        "\x1b[97m{0:─<21}┼{0:─<w$}┼{0:─<12}┼{0:─<8}┼{0:─<40}\x1b[0m",
+        "", w = colw + 2,
+    );
+
+    let mut count = 0;
+    for region in regions {
+        for found in extract(region.content, elf.little_endian, args) {
+            let vaddr = region.vaddr.map(|a| a + found.start as u64);
+            if let Some(ref referenced) = referenced {
+                if !vaddr.is_some_and(|a| referenced.contains(&a)) {
+                    continue;
+                }
+            }
+
+            let vaddr = match vaddr {
+                Some(a) => sp.hex(a).to_string(),
+                None => "-".to_string(),
+            };
+            let enc = match found.encoding {
+                StringEncoding::Ascii => "ascii",
+                StringEncoding::Utf8 => "utf-8",
+                StringEncoding::Utf16 => "utf-16",
+            };
+            println!(
+                "{:20} │ {vaddr:>colw$} │ {:>#10x} │ {enc:6} │ {:?}",
+                region.name, region.offset + found.start as u64, found.text,
+            );
+            count += 1;
+        }
+    }
+
+    println!();
+    println!("{count} string(s)");
+
+    Ok(())
+}
+
+fn regions<'a>(elf: &Elf, bytes: &'a [u8], args: &StringsArgs) -> Result<Vec<Region<'a>>> {
+    if let Some(ref name) = args.section {
+        let sh = find_section(elf, name)
+            .ok_or_else(|| anyhow!("couldn't find section {name:?}"))?;
+        let content = sh.file_range()
+            .and_then(|r| bytes.get(r))
+            .ok_or_else(|| anyhow!("section {name:?} has no content"))?;
+        return Ok(vec![Region {
+            name: name.clone(),
+            vaddr: sh.is_alloc().then_some(sh.sh_addr),
+            offset: sh.sh_offset,
+            content,
+        }]);
+    }
+
+    if args.segments || elf.section_headers.is_empty() {
+        return Ok(elf.program_headers.iter()
+            .enumerate()
+            .filter(|(_, ph)| ph.p_type == PT_LOAD)
+            .filter_map(|(i, ph)| Some(Region {
+                name: format!("LOAD #{i}"),
+                vaddr: Some(ph.p_vaddr),
+                offset: ph.p_offset,
+                content: bytes.get(ph.file_range())?,
+            }))
+            .collect());
+    }
+
+    Ok(elf.section_headers.iter()
+        .filter(|sh| sh.sh_type != SHT_NULL && sh.sh_type != SHT_NOBITS)
+        .filter_map(|sh| Some(Region {
+            name: elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("???").to_string(),
+            vaddr: sh.is_alloc().then_some(sh.sh_addr),
+            offset: sh.sh_offset,
+            content: bytes.get(sh.file_range()?)?,
+        }))
+        .collect())
+}
+
+fn extract(content: &[u8], little_endian: bool, args: &StringsArgs) -> Vec<Found> {
+    let mut found = Vec::new();
+
+    if args.encoding.contains(&StringEncoding::Utf8) {
+        found.extend(extract_utf8(content, args.min_len, false));
+    } else if args.encoding.contains(&StringEncoding::Ascii) {
+        found.extend(extract_utf8(content, args.min_len, true));
+    }
+    if args.encoding.contains(&StringEncoding::Utf16) {
+        found.extend(extract_utf16(content, args.min_len, little_endian));
+    }
+
+    found.sort_by_key(|f| f.start);
+    found
+}
+
+fn is_printable(c: char) -> bool {
+    c == '\t' || !c.is_control()
+}
+
+fn extract_utf8(content: &[u8], min_len: usize, ascii_only: bool) -> Vec<Found> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    let mut text = String::new();
+    let mut pos = 0;
+
+    let mut flush = |start: usize, text: &mut String| {
+        if text.chars().count() >= min_len {
+            let encoding = if text.is_ascii() {
+                StringEncoding::Ascii
+            } else {
+                StringEncoding::Utf8
+            };
+            found.push(Found { start, encoding, text: std::mem::take(text) });
+        }
+        text.clear();
+    };
+
+    while pos < content.len() {
+        let width = match content[pos] {
+            0x00..=0x7f => 1,
+            _ if ascii_only => 0,
+            0xc2..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf4 => 4,
+            _ => 0,
+        };
+        let c = content.get(pos..(pos + width))
+            .filter(|_| width > 0)
+            .and_then(|b| std::str::from_utf8(b).ok())
+            .and_then(|s| s.chars().next())
+            .filter(|&c| is_printable(c));
+
+        match c {
+            Some(c) => {
+                if text.is_empty() {
+                    start = pos;
+                }
+                text.push(c);
+                pos += width;
+            },
+            None => {
+                flush(start, &mut text);
+                pos += 1;
+            },
+        }
+    }
+    flush(start, &mut text);
+
+    found
+}
+
+/// Only characters below U+0800 (Latin, Greek, Cyrillic, Hebrew, Arabic...)
+/// are accepted: any pair of ASCII characters would otherwise be read as a
+/// valid CJK character.
+fn extract_utf16(content: &[u8], min_len: usize, little_endian: bool) -> Vec<Found> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    let mut text = String::new();
+
+    let units = content.chunks_exact(2).map(|b| if little_endian {
+        u16::from_le_bytes([b[0], b[1]])
+    } else {
+        u16::from_be_bytes([b[0], b[1]])
+    });
+
+    let mut flush = |start: usize, text: &mut String| {
+        if text.chars().count() >= min_len {
+            found.push(Found {
+                start,
+                encoding: StringEncoding::Utf16,
+                text: std::mem::take(text),
+            });
+        }
+        text.clear();
+    };
+
+    for c in char::decode_utf16(units) {
+        match c {
+            Ok(c) if is_printable(c) && (c as u32) < 0x800 => {
+                if text.is_empty() {
+                    start = pos;
+                }
+                text.push(c);
+                pos += 2;
+            },
+            Ok(c) => {
+                flush(start, &mut text);
+                pos += c.len_utf16() * 2;
+            },
+            Err(_) => {
+                flush(start, &mut text);
+                pos += 2;
+            },
+        }
+    }
+    flush(start, &mut text);
+
+    found
+}
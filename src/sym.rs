@@ -10,12 +10,20 @@
 
 use goblin::container::Container;
 use goblin::elf::{Elf, Sym, Symtab};
+use goblin::elf::section_header::{SHN_ABS, SHN_COMMON, SHN_UNDEF};
+use goblin::strtab::Strtab;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
 use regex::Regex;
-use rustc_demangle::demangle;
 
+use crate::args::{SymbolColumn, SymbolSort};
+use crate::demangle::demangle;
+use crate::print::{BinSize, PairTable};
 use crate::{print_header, SizePrint, SymbolsArgs};
 
 pub fn all_symbols(elf: &Elf, opts: &SymbolsArgs) {
+    use goblin::elf::sym::*;
+
     let (syms, strtab) = if opts.dynamic {
         (&elf.dynsyms, &elf.dynstrtab)
     } else {
@@ -36,15 +44,31 @@ pub fn all_symbols(elf: &Elf, opts: &SymbolsArgs) {
         Container::Big => 19,
         Container::Little => 11,
     };
-    println!(
-        "\x1b[97m{:>colw$} │ {:7} │ {:10} │ {}\x1b[0m",
-        "Value", "Type VB", "Size", "Name",
-    );
-    println!(
-        "\x1b[97m{0:─<w$}┼{0:─<9}┼{0:─<12}┼{0:─<60}\x1b[0m",
-        "", w = colw + 1,
-    );
+    let width = |column: &SymbolColumn| match column {
+        SymbolColumn::Value => colw,
+        SymbolColumn::Type => 7,
+        SymbolColumn::Size => 10,
+        SymbolColumn::Section => 18,
+        SymbolColumn::Name => 58,
+    };
+    let titles: Vec<String> = opts.columns.iter()
+        .map(|column| match column {
+            SymbolColumn::Value => format!("{:>colw$}", "Value"),
+            SymbolColumn::Type => format!("{:7}", "Type VB"),
+            SymbolColumn::Size => format!("{:10}", "Size"),
+            SymbolColumn::Section => format!("{:18}", "Section"),
+            SymbolColumn::Name if Some(column) == opts.columns.last() => "Name".to_string(),
+            SymbolColumn::Name => format!("{:58}", "Name"),
+        })
+        .collect();
+    let rules: Vec<String> = opts.columns.iter()
+        .enumerate()
+        .map(|(i, column)| "─".repeat(width(column) + if i == 0 { 1 } else { 2 }))
+        .collect();
+    println!("\x1b[97m{}\x1b[0m", titles.join(" │ "));
+    println!("\x1b[97m{}\x1b[0m", rules.join("┼"));
 
+    let mut shown = Vec::new();
     for sym in syms.iter() {
         if (opts.global && sym.st_bind() != STB_GLOBAL)
             || (opts.local && sym.st_bind() != STB_LOCAL)
@@ -58,7 +82,7 @@ pub fn all_symbols(elf: &Elf, opts: &SymbolsArgs) {
             }
         }
 
-        let name = strtab.get_at(sym.st_name).unwrap(); // TODO
+        let name = strtab.get_at(sym.st_name).unwrap_or("<invalid name>");
         let name = if !opts.no_demangle {
             let s = demangle(name).to_string();
             if opts.no_rust_std && is_std_sym(&s) {
@@ -68,7 +92,6 @@ pub fn all_symbols(elf: &Elf, opts: &SymbolsArgs) {
         } else {
             name.to_string()
         };
-        let defined = sym.st_value > 0;
 
         if let Some(ref filter) = opts.filter {
             if !filter.is_match(&name) {
@@ -76,7 +99,22 @@ pub fn all_symbols(elf: &Elf, opts: &SymbolsArgs) {
             }
         }
 
-        use goblin::elf::sym::*;
+        shown.push((sym, name));
+    }
+
+    match opts.sort {
+        None => (),
+        Some(SymbolSort::Addr) => shown.sort_by_key(|(sym, _)| sym.st_value),
+        Some(SymbolSort::Size) => shown.sort_by_key(|(sym, _)| sym.st_size),
+        Some(SymbolSort::Name) => shown.sort_by(|(_, a), (_, b)| a.cmp(b)),
+        Some(SymbolSort::Type) => shown.sort_by_key(|(sym, _)| (sym.st_type(), sym.st_value)),
+    }
+    if opts.reverse {
+        shown.reverse();
+    }
+
+    for (sym, name) in &shown {
+        let defined = sym.st_value > 0;
         let typ = match sym.st_type() {
             STT_NOTYPE => "\x1b[90mNONE\x1b[0m",
             STT_OBJECT => " \x1b[34mOBJ\x1b[0m",
@@ -111,17 +149,39 @@ pub fn all_symbols(elf: &Elf, opts: &SymbolsArgs) {
             0 => "          ".to_string(),
             n => format!("{n:#010x}"),
         };
+        let section = match sym.st_shndx as u32 {
+            SHN_UNDEF => "\x1b[90mUND               \x1b[0m".to_string(),
+            SHN_ABS => "\x1b[90mABS               \x1b[0m".to_string(),
+            SHN_COMMON => "\x1b[90mCOMMON            \x1b[0m".to_string(),
+            idx => match elf.section_headers.get(idx as usize) {
+                Some(sh) => {
+                    let name = elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("<invalid name>");
+                    format!("{name:18}")
+                },
+                None => format!("\x1b[93m#{idx:<17}\x1b[0m"),
+            },
+        };
+
+        let cells: Vec<String> = opts.columns.iter()
+            .map(|column| match column {
+                SymbolColumn::Value => sp.hex(sym.st_value).to_string(),
+                SymbolColumn::Type => format!("{typ} {vis}{bind}"),
+                SymbolColumn::Size => size.clone(),
+                SymbolColumn::Section => section.clone(),
+                SymbolColumn::Name if Some(column) == opts.columns.last() => name.clone(),
+                SymbolColumn::Name => format!("{name:58}"),
+            })
+            .collect();
 
         if !defined {
             print!("\x1b[30m");
         }
-
-        println!(
-            "{v} \x1b[97m│\x1b[0m {typ} {vis}{bind} \x1b[97m│\x1b[0m {size} \x1b[97m│\x1b[0m {name}",
-            v = sp.hex(sym.st_value),
-        );
+        println!("{}", cells.join(" \x1b[97m│\x1b[0m "));
     }
 
+    println!();
+    symbols_summary(&shown);
+
     println!();
     println!("\x1b[97mVisibility [V]:        Binding [B]:\x1b[0m");
     println!("  +  Default             \x1b[90ml\x1b[0m  Local");
@@ -133,12 +193,48 @@ pub fn all_symbols(elf: &Elf, opts: &SymbolsArgs) {
     println!("  e  Eliminate");
 }
 
-fn is_std_sym(sym: &str) -> bool {
-    sym.starts_with("core::")
-        || sym.starts_with("std::")
-        || sym.starts_with("alloc::")
-        || Regex::new("^<(std|core|alloc)::.+ as .+>").unwrap().is_match(sym)
-        || Regex::new("^<.+ as (std|core|alloc)::.+>").unwrap().is_match(sym)
+/// Count the displayed symbols per type and binding, and sum their sizes.
+fn symbols_summary(shown: &[(Sym, String)]) {
+    use goblin::elf::sym::{bind_to_str, type_to_str};
+
+    let mut types = BTreeMap::new();
+    let mut binds = BTreeMap::new();
+    for (sym, _) in shown {
+        *types.entry(sym.st_type()).or_insert(0) += 1;
+        *binds.entry(sym.st_bind()).or_insert(0) += 1;
+    }
+    let counts = |map: BTreeMap<u8, usize>, to_str: fn(u8) -> &'static str| {
+        map.into_iter()
+            .map(|(k, n)| format!("{n} {}", to_str(k)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let total = shown.iter().map(|(sym, _)| sym.st_size).fold(0u64, u64::saturating_add);
+
+    let table = PairTable(10);
+    table.field("Symbols");
+    println!("{}", shown.len());
+    table.field("Types");
+    println!("{}", counts(types, type_to_str));
+    table.field("Bindings");
+    println!("{}", counts(binds, bind_to_str));
+    table.field("Total size");
+    println!("{total:#x} B ({})", BinSize(total));
+}
+
+/// Whether a demangled symbol belongs to Rust's standard libraries. Crates in
+/// v0-mangled names are followed by their hash, e.g. `core[a1b2c3]::`.
+pub fn is_std_sym(sym: &str) -> bool {
+    static STD_SYM: OnceLock<Regex> = OnceLock::new();
+    let re = STD_SYM.get_or_init(|| Regex::new(concat!(
+        r"^(",
+        r"(std|core|alloc)(\[[0-9a-f]+\])?::",
+        r"|<(std|core|alloc)(\[[0-9a-f]+\])?::.+ as .+>",
+        r"|<.+ as (std|core|alloc)(\[[0-9a-f]+\])?::.+>",
+        r")",
+    )).unwrap());
+
+    re.is_match(sym)
 }
 
 pub fn sym_type(typ: u8) -> &'static str {
@@ -172,3 +268,49 @@ pub fn addr_to_sym(symtab: &Symtab, addr: u64) -> Option<Sym> {
         Some(curr_sym)
     }
 }
+
+/// List the symbols a name given by the user matches, when it is ambiguous.
+pub fn print_candidates(elf: &Elf, pattern: &str, matches: &[(Sym, &str)]) {
+    let container = elf.header.container().unwrap_or(Container::Big);
+    let sp = SizePrint::new(container);
+    let colw = match container {
+        Container::Big => 19,
+        Container::Little => 11,
+    };
+
+    print_header(&format!("SYMBOLS MATCHING {pattern:?} ({})", matches.len()));
+    println!("\x1b[97m{:>colw$} │ {:10} │ Name\x1b[0m", "Value", "Size");
+    println!(
+        "\x1b[97m{0:─<w$}┼{0:─<12}┼{0:─<60}\x1b[0m",
+        "", w = colw + 1,
+    );
+    for &(sym, name) in matches {
+        println!(
+            "{} \x1b[97m│\x1b[0m {:#010x} \x1b[97m│\x1b[0m {}",
+            sp.hex(sym.st_value), sym.st_size, demangle(name),
+        );
+    }
+    println!();
+}
+
+/// Find a name for the exact address `addr`: a defined symbol starting at this
+/// address, or else the target symbol of a relocation applied at this address.
+pub fn sym_name_at<'a>(elf: &'a Elf, addr: u64) -> Option<&'a str> {
+    let by_value = |tab: &Symtab, strtab: &'a Strtab<'a>| {
+        tab.iter()
+            .find(|sym| sym.st_value == addr && sym.st_shndx != 0)
+            .and_then(|sym| strtab.get_at(sym.st_name))
+            .filter(|name| !name.is_empty())
+    };
+
+    by_value(&elf.syms, &elf.strtab)
+        .or_else(|| by_value(&elf.dynsyms, &elf.dynstrtab))
+        .or_else(|| {
+            elf.dynrelas.iter()
+                .chain(elf.dynrels.iter())
+                .chain(elf.pltrelocs.iter())
+                .find(|rel| rel.r_offset == addr && rel.r_sym != 0)
+                .and_then(|rel| elf.dynsyms.get(rel.r_sym))
+                .and_then(|sym| elf.dynstrtab.get_at(sym.st_name))
+        })
+}
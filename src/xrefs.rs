@@ -0,0 +1,370 @@
+/******************************************************************************
+ * Copyright © 2023 Kévin Lesénéchal <kevin.lesenechal@gmail.com>             *
+ * This file is part of the elf-info CLI tool.                                *
+ *                                                                            *
+ * elf-info is free software; you can redistribute it and/or modify it under  *
+ * the terms of the GNU General Public License as published by the Free       *
+ * Software Foundation; either version 3 of the License, or (at your option)  *
+ * any later version. See LICENSE file for more information.                  *
+ ******************************************************************************/
+
+//! Cross-references: which instructions reference which code or data
+//! addresses, found by decoding all executable sections.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use anyhow::{anyhow, bail, Context, Result};
+use goblin::container::Container;
+use goblin::elf::Elf;
+use goblin::elf::header::{EM_386, EM_X86_64};
+use goblin::elf::section_header::SHT_NOBITS;
+use iced_x86::{Decoder, DecoderOptions, FlowControl, Formatter, Instruction,
+               OpKind, Register};
+
+use crate::args::XrefsArgs;
+use crate::demangle::demangle;
+use crate::eh::fde_ranges;
+use crate::elf::{find_symbol, find_symbol_by_addr};
+use crate::func::{code_labels, ColorOutput, got_symbols, infer_fn_size,
+                  new_formatter, plt_symbols, resolver_symbols,
+                  rodata_string};
+use crate::print::{print_header, SizePrint};
+use crate::sym::addr_to_sym;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum XrefKind {
+    Call,
+    Jump,
+    CondJump,
+    Data,
+}
+
+impl XrefKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Call => "\x1b[33mcall\x1b[0m",
+            Self::Jump => "\x1b[34mjump\x1b[0m",
+            Self::CondJump => "\x1b[34mbranch\x1b[0m",
+            Self::Data => "\x1b[36mdata\x1b[0m",
+        }
+    }
+}
+
+pub struct Xref {
+    pub instr: Instruction,
+    pub target: u64,
+    pub kind: XrefKind,
+}
+
+impl Xref {
+    pub fn site(&self) -> u64 {
+        self.instr.ip()
+    }
+}
+
+/// Decode every executable section and collect the direct calls and jumps,
+/// and the absolute or RIP-relative memory operands. Calls and jumps through
+/// a memory slot (e.g. `call *puts@GOTPCREL(%rip)`) target that slot.
+pub fn code_xrefs(elf: &Elf, bytes: &[u8]) -> Result<Vec<Xref>> {
+    let mut xrefs = Vec::new();
+    decode_code(elf, bytes, |instr| {
+        if let Some((kind, target)) = instr_xref(&instr) {
+            xrefs.push(Xref { instr, target, kind });
+        }
+    })?;
+
+    Ok(xrefs)
+}
+
+/// The code labels of all executable sections, see `code_labels`.
+pub fn exec_labels(elf: &Elf, bytes: &[u8], fdes: &[(u64, u64)]) -> BTreeMap<u64, String> {
+    elf.section_headers.iter()
+        .filter(|sh| sh.is_executable())
+        .flat_map(|sh| {
+            let end = sh.sh_addr.saturating_add(sh.sh_size);
+            code_labels(elf, bytes, fdes, sh.sh_addr..end)
+        })
+        .collect()
+}
+
+/// The virtual address and content of every executable section.
+pub fn exec_sections<'a>(elf: &Elf, bytes: &'a [u8]) -> Result<Vec<(u64, &'a [u8])>> {
+    if !matches!(elf.header.e_machine, EM_386 | EM_X86_64) {
+        bail!("code analysis is only supported for x86 and x86-64");
+    }
+
+    Ok(elf.section_headers.iter()
+        .filter(|sh| sh.is_executable() && sh.sh_type != SHT_NOBITS)
+        .filter_map(|sh| Some((sh.sh_addr, bytes.get(sh.file_range()?)?)))
+        .collect())
+}
+
+/// Decode all instructions of every executable section, in order.
+pub fn decode_code(
+    elf: &Elf,
+    bytes: &[u8],
+    mut f: impl FnMut(Instruction),
+) -> Result<()> {
+    let bitness = if elf.is_64 { 64 } else { 32 };
+
+    for (addr, content) in exec_sections(elf, bytes)? {
+        let decoder = Decoder::with_ip(bitness, content, addr, DecoderOptions::NONE);
+        decoder.into_iter().for_each(&mut f);
+    }
+
+    Ok(())
+}
+
+pub fn instr_xref(instr: &Instruction) -> Option<(XrefKind, u64)> {
+    let branch_kind = match instr.flow_control() {
+        FlowControl::Call | FlowControl::IndirectCall => Some(XrefKind::Call),
+        FlowControl::UnconditionalBranch
+        | FlowControl::IndirectBranch => Some(XrefKind::Jump),
+        FlowControl::ConditionalBranch => Some(XrefKind::CondJump),
+        _ => None,
+    };
+
+    if let Some(kind) = branch_kind {
+        if matches!(
+            instr.op0_kind(),
+            OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64,
+        ) {
+            return Some((kind, instr.near_branch_target()));
+        }
+    }
+
+    let mem = (0..instr.op_count()).any(|i| instr.op_kind(i) == OpKind::Memory);
+    if !mem || instr.memory_index() != Register::None {
+        return None;
+    }
+    match instr.memory_base() {
+        Register::RIP | Register::EIP | Register::None => Some((
+            branch_kind.unwrap_or(XrefKind::Data),
+            instr.memory_displacement64(),
+        )),
+        _ => None,
+    }
+}
+
+pub fn xrefs(elf: &Elf, bytes: &[u8], args: &XrefsArgs) -> Result<()> {
+    let fdes = fde_ranges(elf, bytes);
+    let labels = exec_labels(elf, bytes, &fdes);
+    let all = code_xrefs(elf, bytes)?;
+    let names = Names {
+        elf,
+        sp: SizePrint::new(elf.header.container().unwrap_or(Container::Big)),
+        labels: &labels,
+        got: got_symbols(elf),
+    };
+
+    if args.from {
+        let (name, range) = function_range(elf, bytes, &fdes, &labels, args)?;
+        xrefs_from(&names, bytes, &name, range, &all);
+    } else {
+        let (name, targets) = target_ranges(elf, bytes, &names, args)?;
+        let mut formatter = new_formatter(resolver_symbols(elf, bytes), args.syntax);
+        xrefs_to(&names, &name, &targets, &all, formatter.as_mut());
+    }
+
+    Ok(())
+}
+
+fn xrefs_to(
+    names: &Names,
+    name: &str,
+    targets: &[Range<u64>],
+    all: &[Xref],
+    formatter: &mut dyn Formatter,
+) {
+    let mut by_fn: BTreeMap<Option<u64>, Vec<&Xref>> = BTreeMap::new();
+    for xref in all {
+        let Some(target) = targets.iter().find(|t| t.contains(&xref.target)) else {
+            continue
+        };
+        // Jumps within the function itself are not references to it.
+        let internal_jump = matches!(xref.kind, XrefKind::Jump | XrefKind::CondJump)
+            && target.contains(&xref.site());
+        if internal_jump {
+            continue;
+        }
+
+        let func = names.labels.range(..=xref.site()).next_back().map(|(&a, _)| a);
+        by_fn.entry(func).or_default().push(xref);
+    }
+
+    print_header(&format!("XREFS TO {name}"));
+    if by_fn.is_empty() {
+        println!("No references found.");
+        return;
+    }
+
+    let mut count = 0;
+    for (func, xrefs) in &by_fn {
+        let func_name = func.and_then(|f| names.labels.get(&f))
+            .map(String::as_str)
+            .unwrap_or("???");
+        println!("\x1b[97m{func_name}\x1b[0m:");
+
+        for xref in xrefs {
+            print!(
+                "  {} \x1b[97m│\x1b[0m {:<15} \x1b[97m│\x1b[0m  ",
+                names.sp.hex(xref.site()), xref.kind.name(),
+            );
+            formatter.format(&xref.instr, &mut ColorOutput);
+            println!("\x1b[0m");
+        }
+        count += xrefs.len();
+    }
+
+    println!();
+    println!("{count} reference(s) in {} function(s)", by_fn.len());
+}
+
+fn xrefs_from(
+    names: &Names,
+    bytes: &[u8],
+    name: &str,
+    range: Range<u64>,
+    all: &[Xref],
+) {
+    print_header(&format!("XREFS FROM {name}"));
+
+    let mut count = 0;
+    for xref in all {
+        if !range.contains(&xref.site()) {
+            continue;
+        }
+        let internal_jump = matches!(xref.kind, XrefKind::Jump | XrefKind::CondJump)
+            && range.contains(&xref.target);
+        if internal_jump {
+            continue;
+        }
+
+        print!(
+            "{} \x1b[97m│\x1b[0m {:<15} \x1b[97m│\x1b[0m {}",
+            names.sp.hex(xref.site()), xref.kind.name(), names.name(xref.target),
+        );
+        match rodata_string(names.elf, bytes, xref.target) {
+            Some(s) if xref.kind == XrefKind::Data => println!("  \x1b[90m# {s}\x1b[0m"),
+            _ => println!(),
+        }
+        count += 1;
+    }
+
+    if count == 0 {
+        println!("No references found.");
+    }
+}
+
+/// The address ranges that count as references to the requested symbol: the
+/// symbol itself, and its PLT stubs and GOT slots for imported symbols.
+fn target_ranges(
+    elf: &Elf,
+    bytes: &[u8],
+    names: &Names,
+    args: &XrefsArgs,
+) -> Result<(String, Vec<Range<u64>>)> {
+    if args.address {
+        let addr = parse_addr(&args.name)?;
+        let target = addr..(addr + 1);
+        return Ok((names.name(addr), vec![target]));
+    }
+
+    let matches = |raw: &str| raw == args.name || demangle(raw).to_string() == args.name;
+    let mut targets = Vec::new();
+
+    let tabs = [(&elf.syms, &elf.strtab), (&elf.dynsyms, &elf.dynstrtab)];
+    for (syms, strtab) in tabs {
+        if let Some(sym) = find_symbol(syms, strtab, &args.name) {
+            if sym.st_shndx != 0 {
+                targets.push(sym.st_value..(sym.st_value + sym.st_size.max(1)));
+            }
+        }
+    }
+    for (addr, name) in plt_symbols(elf, bytes) {
+        if name.strip_suffix("@plt").is_some_and(matches) {
+            targets.push(addr..(addr + 1));
+        }
+    }
+    let ptr_size = if elf.is_64 { 8 } else { 4 };
+    for (addr, name) in &names.got {
+        if matches(name) {
+            targets.push(*addr..(addr + ptr_size));
+        }
+    }
+
+    if targets.is_empty() {
+        bail!("couldn't find any symbol matching {:?}", args.name);
+    }
+
+    Ok((args.name.clone(), targets))
+}
+
+/// The name and address range of the function to list references from.
+fn function_range(
+    elf: &Elf,
+    bytes: &[u8],
+    fdes: &[(u64, u64)],
+    labels: &BTreeMap<u64, String>,
+    args: &XrefsArgs,
+) -> Result<(String, Range<u64>)> {
+    let (name, start, size) = if args.address {
+        let addr = parse_addr(&args.name)?;
+        if let Some(sym) = find_symbol_by_addr(&elf.syms, addr) {
+            let name = elf.strtab.get_at(sym.st_name).unwrap_or("???");
+            (demangle(name).to_string(), sym.st_value, sym.st_size)
+        } else {
+            let (&start, name) = labels.range(..=addr).next_back()
+                .ok_or_else(|| anyhow!("couldn't find any function containing {addr:#x}"))?;
+            (name.clone(), start, 0)
+        }
+    } else {
+        let sym = find_symbol(&elf.syms, &elf.strtab, &args.name)
+            .or_else(|| find_symbol(&elf.dynsyms, &elf.dynstrtab, &args.name))
+            .filter(|sym| sym.st_shndx != 0)
+            .ok_or_else(|| anyhow!("couldn't find any function matching {:?}", args.name))?;
+        (args.name.clone(), sym.st_value, sym.st_size)
+    };
+
+    let size = if size == 0 {
+        infer_fn_size(elf, bytes, fdes, start)
+            .ok_or_else(|| anyhow!("couldn't infer the size of {name:?}"))?
+    } else {
+        size
+    };
+
+    Ok((name, start..(start + size)))
+}
+
+fn parse_addr(s: &str) -> Result<u64> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16)
+        .context(anyhow!("couldn't parse memory address '{s}'"))
+}
+
+struct Names<'a> {
+    elf: &'a Elf<'a>,
+    sp: SizePrint,
+    labels: &'a BTreeMap<u64, String>,
+    got: HashMap<u64, String>,
+}
+
+impl Names<'_> {
+    fn name(&self, addr: u64) -> String {
+        if let Some(label) = self.labels.get(&addr) {
+            return label.clone();
+        }
+        if let Some(name) = self.got.get(&addr) {
+            return format!("{}@got", demangle(name));
+        }
+
+        let sym = addr_to_sym(&self.elf.syms, addr)
+            .filter(|sym| sym.st_shndx != 0)
+            .and_then(|sym| Some((sym, self.elf.strtab.get_at(sym.st_name)?)))
+            .filter(|(_, name)| !name.is_empty());
+        match sym {
+            Some((sym, name)) if sym.st_value == addr => demangle(name).to_string(),
+            Some((sym, name)) => format!("{} + {:#x}", demangle(name), addr - sym.st_value),
+            None => self.sp.hex(addr).to_string(),
+        }
+    }
+}